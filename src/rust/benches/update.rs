@@ -0,0 +1,158 @@
+//! Timings of the hot paths of a tick at 1k to 1M people, run with `cargo bench`.
+//! Pick some with a filter, e.g. `cargo bench -- infect_closeby/threads/100000`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use sir::sir::neighbours::SpatialIndex;
+use sir::sir::person::{Person, PersonState};
+use sir::sir::population::Population;
+use sir::sir::seeding::Seeding;
+use sir::sir::virus::{Strain, Strains, Virus};
+use sir::sir::world::{PopulationDistribution, World};
+
+const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+// Cell sizes as a multiple of the distance of the virus
+const CELL_SIZES: [f32; 3] = [1.0, 2.0, 4.0];
+const INFECTIOUS: f32 = 0.01;
+const MOVE_SPEED: f32 = 5.0;
+
+// Every tick costs the same: 1 in 100 people stays infectious and nobody gets infected,
+// all contacts are still looked for. The world grows with the population, so everybody
+// has about 3 people within the distance of the virus.
+fn virus() -> Virus {
+    let mut virus = Virus::corona();
+    virus.infection_rate = 0.0;
+    virus.recovery_time = usize::MAX / 2;
+    virus
+}
+
+fn strains() -> (Strains, Strain) {
+    let mut strains = Strains::new();
+    let strain = strains.add(virus());
+    (strains, strain)
+}
+
+fn world_size(people: usize) -> f32 {
+    10.0 * (people as f32).sqrt()
+}
+
+// 1, 2, 4, ... up to all the threads of this machine
+fn thread_counts() -> Vec<usize> {
+    let available = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut counts: Vec<usize> = (0..).map(|power| 1 << power).take_while(|threads| *threads < available).collect();
+    counts.push(available);
+    counts
+}
+
+fn population(people: usize, cell_size: f32, threads: usize, strain: Strain) -> Population {
+    let size = world_size(people);
+    let cells = (size / (virus().distance * cell_size)).floor().max(1.0) as usize;
+    let mut population = Population::new(size, size, cells, cells);
+    let every = (1.0 / INFECTIOUS) as usize;
+    for index in 0..people {
+        let mut person = Person::new_random(size, size, index);
+        if index % every == 0 {
+            person.set_state(PersonState::Infectious(strain));
+        }
+        population.add(person).unwrap();
+    }
+    population.set_seed(42);
+    population.set_threads(threads).unwrap();
+    population
+}
+
+fn world(people: usize, threads: usize, distribution: PopulationDistribution) -> World {
+    let size = world_size(people);
+    let mut world = World::new(people, size, size, virus(), distribution);
+    world.seed(&Seeding::fractions(people, INFECTIOUS, 0.0, 0.0));
+    world.config(MOVE_SPEED);
+    world.set_seed(42);
+    world.use_threads(threads).unwrap();
+    world
+}
+
+fn bench_population<F>(c: &mut Criterion, name: &str, tick: F)
+where
+    F: Fn(&mut Population, &Strains),
+{
+    let all_threads = *thread_counts().last().unwrap();
+    let (strains, strain) = strains();
+    // the people are only made for the benchmarks that pass the filter
+    let mut group = c.benchmark_group(format!("{}/cells", name));
+    group.sample_size(10);
+    for people in SIZES.iter() {
+        for cell_size in CELL_SIZES.iter() {
+            let mut population = None;
+            let id = BenchmarkId::new(people.to_string(), format!("{}x distance", cell_size));
+            group.bench_function(id, |b| {
+                let population = population.get_or_insert_with(|| self::population(*people, *cell_size, all_threads, strain));
+                b.iter(|| tick(population, &strains))
+            });
+        }
+    }
+    group.finish();
+    let mut group = c.benchmark_group(format!("{}/threads", name));
+    group.sample_size(10);
+    for people in SIZES.iter() {
+        for threads in thread_counts() {
+            let mut population = None;
+            let id = BenchmarkId::new(people.to_string(), format!("{} threads", threads));
+            group.bench_function(id, |b| {
+                let population = population.get_or_insert_with(|| self::population(*people, 1.0, threads, strain));
+                b.iter(|| tick(population, &strains))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn update_positions(c: &mut Criterion) {
+    bench_population(c, "update_positions", |population, strains| {
+        population.update_positions(MOVE_SPEED, &[], strains)
+    });
+}
+
+fn infect_closeby(c: &mut Criterion) {
+    bench_population(c, "infect_closeby", |population, strains| {
+        population.infect_closeby(&[], strains);
+    });
+}
+
+fn update_world(c: &mut Criterion) {
+    let mut group = c.benchmark_group("world_update");
+    group.sample_size(10);
+    for people in SIZES.iter() {
+        for threads in thread_counts() {
+            let mut world = None;
+            let id = BenchmarkId::new(people.to_string(), format!("{} threads", threads));
+            group.bench_function(id, |b| {
+                let world = world.get_or_insert_with(|| self::world(*people, threads, PopulationDistribution::Random));
+                b.iter(|| world.update())
+            });
+        }
+    }
+    group.finish();
+}
+
+// Crowded clusters, where some cells of the grid hold many people
+fn spatial_index(c: &mut Criterion) {
+    let all_threads = *thread_counts().last().unwrap();
+    let mut group = c.benchmark_group("spatial_index");
+    group.sample_size(10);
+    for people in SIZES[..3].iter() {
+        for index in [SpatialIndex::Grid, SpatialIndex::KdTree].iter() {
+            let mut world = None;
+            let id = BenchmarkId::new(people.to_string(), format!("{:?}", index));
+            group.bench_function(id, |b| {
+                let world = world.get_or_insert_with(|| {
+                    let mut world = self::world(*people, all_threads, PopulationDistribution::Clustered);
+                    world.set_spatial_index(*index);
+                    world
+                });
+                b.iter(|| world.update())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, update_world, update_positions, infect_closeby, spatial_index);
+criterion_main!(benches);
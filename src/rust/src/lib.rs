@@ -1,2 +1,6 @@
 #[macro_use]
 pub mod sir;
+
+// Lets JavaScript start the thread pool with `initThreadPool(navigator.hardwareConcurrency)`
+#[cfg(all(target_arch = "wasm32", feature = "wasm-threads"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
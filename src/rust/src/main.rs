@@ -1,14 +1,27 @@
+use sir::sir::demographics::Demographics;
+use sir::sir::density::DensityMap;
+use sir::sir::neighbours::SpatialIndex;
+use sir::sir::policy::Policy;
 use sir::sir::virus::Virus;
-use sir::sir::world::{PopulationDistribution, World};
+use sir::sir::world::{DistributionSettings, PopulationDistribution, World};
 // use ::rendering::canvas_render::render_world;
 extern crate argparse;
-use argparse::{ArgumentParser, Store, StoreOption};
+use argparse::{ArgumentParser, Collect, Store, StoreOption, StoreTrue};
 
 fn main() {
     let mut request_width: Option<usize> = None;
     let mut request_height: Option<usize> = None;
     let mut request_population: Option<usize> = None;
     let mut population_distribution = "random".to_string();
+    let mut spatial_index = "grid".to_string();
+    let mut density_map: Option<String> = None;
+    let mut density_blocks_movement = false;
+    let mut policies: Vec<String> = Vec::new();
+    let mut birth_rate = 0.0;
+    let mut death_rate = 0.0;
+    let mut seed: Option<u32> = None;
+    let mut threads = 0;
+    let mut ticks = 0;
     let graph_size = 200;
     let mut width = 1920;
     let mut population = 1000;
@@ -35,7 +48,52 @@ fn main() {
         ap.refer(&mut population_distribution).add_option(
             &["-d", "--distribution"],
             Store,
-            "Distribution of people in the world (random or grid)",
+            "Distribution of people in the world (random, grid, density, clustered or households)",
+        );
+        ap.refer(&mut spatial_index).add_option(
+            &["--spatial-index"],
+            Store,
+            "How people near each other are found (grid or kdtree, default is grid)",
+        );
+        ap.refer(&mut density_map).add_option(
+            &["--density-map"],
+            StoreOption,
+            "Csv or pgm file with the population density, used by the density distribution",
+        );
+        ap.refer(&mut density_blocks_movement).add_option(
+            &["--density-blocks-movement"],
+            StoreTrue,
+            "People can't walk into or through places without any density",
+        );
+        ap.refer(&mut policies).add_option(
+            &["--policy"],
+            Collect,
+            "Intervention policy, e.g. lockdown,0.2,start=50,end=150 or masks,0.5,trigger=0.05,duration=100",
+        );
+        ap.refer(&mut birth_rate).add_option(
+            &["--birth-rate"],
+            Store,
+            "Children born per person per tick (default is 0)",
+        );
+        ap.refer(&mut death_rate).add_option(
+            &["--death-rate"],
+            Store,
+            "Chance per tick that a person dies of other causes than the virus (default is 0)",
+        );
+        ap.refer(&mut seed).add_option(
+            &["--seed"],
+            StoreOption,
+            "Seed for the movement of the people only, runs with the same seed move the same way but infections, testing, vaccination, births and deaths still differ",
+        );
+        ap.refer(&mut threads).add_option(
+            &["--threads"],
+            Store,
+            "Number of threads to use (default is all available cores)",
+        );
+        ap.refer(&mut ticks).add_option(
+            &["-t", "--ticks"],
+            Store,
+            "Number of ticks to simulate, the stats of every tick are printed as csv",
         );
         ap.parse_args_or_exit();
     }
@@ -54,8 +112,79 @@ fn main() {
     if population_distribution == "grid" {
         distribution = PopulationDistribution::Grid;
     }
+    if population_distribution == "density" {
+        distribution = PopulationDistribution::Density;
+    }
+    if population_distribution == "clustered" {
+        distribution = PopulationDistribution::Clustered;
+    }
+    if population_distribution == "households" {
+        distribution = PopulationDistribution::Households;
+    }
+    let mut settings = DistributionSettings::new();
+    settings.density_blocks_movement = density_blocks_movement;
+    if let Some(path) = density_map {
+        let density = match std::fs::read(&path) {
+            Ok(bytes) if path.ends_with(".pgm") => DensityMap::from_pgm(&bytes),
+            Ok(bytes) => DensityMap::from_csv(&String::from_utf8_lossy(&bytes)),
+            Err(error) => Err(error.to_string()),
+        };
+        match density {
+            Ok(density) => settings.set_density_map(density),
+            Err(error) => {
+                eprintln!("Could not load density map {}: {}", path, error);
+                std::process::exit(1);
+            }
+        }
+    }
     let virus = Virus::corona();
-    let mut world = World::new(population, width as f32, height as f32, virus, distribution);
+    let mut world = World::new_with_settings(population, width as f32, height as f32, virus, distribution, settings);
     world.config(15.0);
+    if spatial_index == "kdtree" {
+        world.set_spatial_index(SpatialIndex::KdTree);
+    }
+    if let Some(seed) = seed {
+        world.set_seed(seed);
+    }
+    if let Err(error) = world.use_threads(threads) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    for policy in policies {
+        match policy.parse::<Policy>() {
+            Ok(policy) => {
+                world.add_policy(policy);
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+    if birth_rate > 0.0 || death_rate > 0.0 {
+        world.set_demographics(Demographics::new(birth_rate, death_rate));
+    }
+    if ticks > 0 {
+        println!("tick,susceptible,exposed,infected,recovered,vaccinated,cases,detected,averted,policies");
+        for _ in 0..ticks {
+            // printed right away, long runs go beyond the ticks the history keeps
+            world.update();
+            let record = world.record(world.get_tick() - 1).unwrap();
+            let policies: Vec<String> = record.active_policies.iter().map(|index| index.to_string()).collect();
+            println!(
+                "{},{},{},{},{},{},{},{},{},{}",
+                record.tick,
+                record.stats.susceptable,
+                record.stats.exposed,
+                record.stats.infected,
+                record.stats.recovered,
+                record.stats.vaccinated,
+                record.stats.cases,
+                record.stats.detected,
+                record.stats.averted,
+                policies.join(" ")
+            );
+        }
+    }
     // render_world(world, width, height, graph_size);
 }
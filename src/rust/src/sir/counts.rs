@@ -0,0 +1,90 @@
+use super::person::{Aged, Person, PersonState};
+use std::ops::{AddAssign, SubAssign};
+
+/// The number of people in each state, the population keeps them up to date while people change
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub susceptible: usize,
+    pub exposed: usize,
+    pub infectious: usize,
+    /// Everybody that recovered, including the dead
+    pub recovered: usize,
+    pub dead: usize,
+    pub vaccinated: usize,
+    /// Everybody that ever got infected
+    pub cases: usize,
+    pub detected: usize,
+    pub isolated: usize,
+}
+
+impl Counts {
+    /// The counts of a single person
+    pub fn of(person: &Person) -> Counts {
+        let mut counts = Counts::of_state(person.get_state());
+        counts.vaccinated = person.is_vaccinated() as usize;
+        counts.cases = person.was_infected() as usize;
+        counts.detected = person.is_detected() as usize;
+        counts.isolated = person.is_isolated() as usize;
+        counts
+    }
+    fn of_state(state: PersonState) -> Counts {
+        let mut counts = Counts::default();
+        match state {
+            PersonState::Susceptible => counts.susceptible = 1,
+            PersonState::Exposed(_) => counts.exposed = 1,
+            PersonState::Infectious(_) => counts.infectious = 1,
+            PersonState::Recovered(dead) => {
+                counts.recovered = 1;
+                counts.dead = dead as usize;
+            }
+        }
+        counts
+    }
+    /// The counts a person left and the counts the person entered while ageing a tick,
+    /// `state` is the state of the person afterwards
+    pub fn aged(state: PersonState, aged: &Aged) -> (Counts, Counts) {
+        let (mut left, mut entered) = (Counts::default(), Counts::default());
+        if let Some(before) = aged.changed_from {
+            left = Counts::of_state(before);
+            entered = Counts::of_state(state);
+        }
+        left.isolated = aged.released as usize;
+        (left, entered)
+    }
+    pub fn total(&self) -> usize {
+        self.susceptible + self.exposed + self.infectious + self.recovered
+    }
+    /// Replaces the counts of a person as it was with the counts of the person as it is now
+    pub fn change(&mut self, before: &Counts, after: &Counts) {
+        *self += *after;
+        *self -= *before;
+    }
+}
+
+impl AddAssign for Counts {
+    fn add_assign(&mut self, other: Counts) {
+        self.susceptible += other.susceptible;
+        self.exposed += other.exposed;
+        self.infectious += other.infectious;
+        self.recovered += other.recovered;
+        self.dead += other.dead;
+        self.vaccinated += other.vaccinated;
+        self.cases += other.cases;
+        self.detected += other.detected;
+        self.isolated += other.isolated;
+    }
+}
+
+impl SubAssign for Counts {
+    fn sub_assign(&mut self, other: Counts) {
+        self.susceptible -= other.susceptible;
+        self.exposed -= other.exposed;
+        self.infectious -= other.infectious;
+        self.recovered -= other.recovered;
+        self.dead -= other.dead;
+        self.vaccinated -= other.vaccinated;
+        self.cases -= other.cases;
+        self.detected -= other.detected;
+        self.isolated -= other.isolated;
+    }
+}
@@ -0,0 +1,46 @@
+use wasm_bindgen::prelude::*;
+
+/// Births and deaths that have nothing to do with the virus.
+/// Both rates are per living person per tick.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Demographics {
+    pub birth_rate: f32,
+    /// Background mortality, people that die of other causes leave the population
+    pub death_rate: f32,
+    // births that didn't add up to a whole person yet
+    pending_births: f32,
+}
+
+#[wasm_bindgen]
+impl Demographics {
+    pub fn new(birth_rate: f32, death_rate: f32) -> Demographics {
+        Demographics {
+            birth_rate,
+            death_rate,
+            pending_births: 0.0,
+        }
+    }
+}
+
+impl Demographics {
+    /// The number of children born during a tick in a population of `living` people
+    pub fn births(&mut self, living: usize) -> usize {
+        self.pending_births += self.birth_rate * living as f32;
+        let births = self.pending_births.floor();
+        self.pending_births -= births;
+        births as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_births_add_up() {
+        let mut demographics = Demographics::new(0.25, 0.0);
+        let births: Vec<usize> = (0..5).map(|_| demographics.births(2)).collect();
+        assert_eq!(births, vec![0, 1, 0, 1, 0]);
+    }
+}
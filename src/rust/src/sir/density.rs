@@ -0,0 +1,313 @@
+use super::geometry::Dimensions;
+use super::person::Location;
+use rand::Rng;
+use wasm_bindgen::prelude::*;
+
+/// A grid of population densities, stretched out over the whole world.
+/// Cell (0, 0) is the top left corner of the world.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct DensityMap {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    // running sum of the values, used to pick a cell proportional to its density
+    cumulative: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl DensityMap {
+    /// Parses a csv file, see `DensityMap::from_csv`
+    pub fn parse_csv(text: &str) -> Result<DensityMap, JsValue> {
+        DensityMap::from_csv(text).map_err(|error| JsValue::from_str(&error))
+    }
+    /// Parses a pgm image, see `DensityMap::from_pgm`
+    pub fn parse_pgm(bytes: &[u8]) -> Result<DensityMap, JsValue> {
+        DensityMap::from_pgm(bytes).map_err(|error| JsValue::from_str(&error))
+    }
+    /// Parses rgba pixels, see `DensityMap::from_rgba`
+    pub fn parse_rgba(width: usize, height: usize, pixels: &[u8]) -> Result<DensityMap, JsValue> {
+        DensityMap::from_rgba(width, height, pixels).map_err(|error| JsValue::from_str(&error))
+    }
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+}
+
+impl DensityMap {
+    pub fn new(width: usize, height: usize, values: Vec<f32>) -> Result<DensityMap, String> {
+        if width == 0 || height == 0 {
+            return Err("A density map needs at least one cell".to_string());
+        }
+        let cells = DensityMap::cells(width, height)?;
+        if values.len() != cells {
+            return Err(format!(
+                "Expected {} density values for a {}x{} map, got {}",
+                cells,
+                width,
+                height,
+                values.len()
+            ));
+        }
+        if values.iter().any(|value| !value.is_finite() || *value < 0.0) {
+            return Err("Densities must be positive numbers".to_string());
+        }
+        let mut cumulative = Vec::with_capacity(values.len());
+        let mut total = 0.0;
+        for value in values.iter() {
+            total += value;
+            cumulative.push(total);
+        }
+        if total <= 0.0 {
+            return Err("The density map is empty".to_string());
+        }
+        Ok(DensityMap {
+            width,
+            height,
+            values,
+            cumulative,
+        })
+    }
+    // The number of cells of a width x height map, which may not fit in memory for a broken file
+    fn cells(width: usize, height: usize) -> Result<usize, String> {
+        width
+            .checked_mul(height)
+            .ok_or_else(|| format!("A {}x{} density map is too large", width, height))
+    }
+    /// Reads a grid of densities, one row per line with the values separated by commas.
+    pub fn from_csv(text: &str) -> Result<DensityMap, String> {
+        let mut values = Vec::new();
+        let mut width = 0;
+        let mut height = 0;
+        for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+            let row = line
+                .split(',')
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<f32>()
+                        .map_err(|_| format!("Invalid density '{}' on row {}", value.trim(), height + 1))
+                })
+                .collect::<Result<Vec<f32>, String>>()?;
+            if height == 0 {
+                width = row.len();
+            } else if row.len() != width {
+                return Err(format!("Row {} has {} values, expected {}", height + 1, row.len(), width));
+            }
+            values.extend(row);
+            height += 1;
+        }
+        DensityMap::new(width, height, values)
+    }
+    /// Reads a grey scale image in the plain (P2) or binary (P5) pgm format,
+    /// lighter pixels have a higher density.
+    pub fn from_pgm(bytes: &[u8]) -> Result<DensityMap, String> {
+        let mut position = 0;
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            let token = DensityMap::next_token(bytes, &mut position)
+                .ok_or_else(|| "Incomplete pgm header".to_string())?;
+            header.push(token);
+        }
+        let parse = |token: &str| token.parse::<usize>().map_err(|_| format!("Invalid pgm header value '{}'", token));
+        let (width, height, max_value) = (parse(&header[1])?, parse(&header[2])?, parse(&header[3])?);
+        if max_value == 0 || max_value > 65535 {
+            return Err(format!("Invalid pgm maximum value {}", max_value));
+        }
+        // the header may be wrong, so only the values that are really there take memory
+        let cells = DensityMap::cells(width, height)?;
+        let mut values = Vec::new();
+        match header[0].as_str() {
+            "P2" => {
+                while let Some(token) = DensityMap::next_token(bytes, &mut position) {
+                    values.push(parse(&token)? as f32 / max_value as f32);
+                }
+            }
+            "P5" => {
+                // a single whitespace character separates the header from the pixels
+                let pixels = &bytes[(position + 1).min(bytes.len())..];
+                if max_value < 256 {
+                    values.extend(pixels.iter().map(|pixel| *pixel as f32 / max_value as f32));
+                } else {
+                    values.extend(
+                        pixels
+                            .chunks_exact(2)
+                            .map(|pixel| (((pixel[0] as usize) << 8) + pixel[1] as usize) as f32 / max_value as f32),
+                    );
+                }
+                values.truncate(cells);
+            }
+            magic => return Err(format!("Unsupported image format '{}', expected P2 or P5", magic)),
+        }
+        DensityMap::new(width, height, values)
+    }
+    /// Reads the pixels of an image as red, green, blue and alpha bytes, e.g. the `ImageData` of a png
+    /// drawn on a canvas, lighter pixels have a higher density and transparent pixels are empty.
+    pub fn from_rgba(width: usize, height: usize, pixels: &[u8]) -> Result<DensityMap, String> {
+        let bytes = DensityMap::cells(width, height)?
+            .checked_mul(4)
+            .ok_or_else(|| format!("A {}x{} rgba image is too large", width, height))?;
+        if pixels.len() != bytes {
+            return Err(format!(
+                "Expected {} bytes for a {}x{} rgba image, got {}",
+                bytes,
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+        let values = pixels
+            .chunks_exact(4)
+            .map(|pixel| {
+                let grey = (pixel[0] as f32 + pixel[1] as f32 + pixel[2] as f32) / (3.0 * 255.0);
+                grey * pixel[3] as f32 / 255.0
+            })
+            .collect();
+        DensityMap::new(width, height, values)
+    }
+    // Reads the next whitespace separated word of a pgm header, skipping comments
+    fn next_token(bytes: &[u8], position: &mut usize) -> Option<String> {
+        loop {
+            while *position < bytes.len() && bytes[*position].is_ascii_whitespace() {
+                *position += 1;
+            }
+            if *position < bytes.len() && bytes[*position] == b'#' {
+                while *position < bytes.len() && bytes[*position] != b'\n' {
+                    *position += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        let start = *position;
+        while *position < bytes.len() && !bytes[*position].is_ascii_whitespace() {
+            *position += 1;
+        }
+        if start == *position {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&bytes[start..*position]).to_string())
+    }
+    fn cell(&self, x: f32, y: f32, world_width: f32, world_height: f32) -> usize {
+        let cell_x = ((x / world_width * self.width as f32).floor().max(0.0) as usize).min(self.width - 1);
+        let cell_y = ((y / world_height * self.height as f32).floor().max(0.0) as usize).min(self.height - 1);
+        cell_y * self.width + cell_x
+    }
+    pub fn density_at(&self, x: f32, y: f32, world_width: f32, world_height: f32) -> f32 {
+        self.values[self.cell(x, y, world_width, world_height)]
+    }
+    /// Nobody can live in or walk through places without any density
+    pub fn is_passable(&self, x: f32, y: f32, world_width: f32, world_height: f32) -> bool {
+        self.density_at(x, y, world_width, world_height) > 0.0
+    }
+    /// Whether every place along the step from `from` to `to` is passable, so that fast people
+    /// can't jump over a narrow strip without any density
+    pub fn is_step_passable(&self, from: &Location, to: &Location, world: &Dimensions) -> bool {
+        let (diff_x, diff_y) = world.delta(from, to);
+        let cell_width = world.width / self.width as f32;
+        let cell_height = world.height / self.height as f32;
+        // at least two samples per cell crossed, so a single cell can't be skipped
+        let steps = ((diff_x.abs() / cell_width).max(diff_y.abs() / cell_height) * 2.0).ceil().max(1.0) as usize;
+        (1..=steps).all(|step| {
+            let fraction = step as f32 / steps as f32;
+            let place = world.wrap(&Location { x: from.x + diff_x * fraction, y: from.y + diff_y * fraction });
+            self.is_passable(place.x, place.y, world.width, world.height)
+        })
+    }
+    /// Picks a random place in the world, places with a higher density are picked more often
+    pub fn random_location(&self, world_width: f32, world_height: f32) -> Location {
+        let mut rng = rand::thread_rng();
+        let total = self.cumulative[self.cumulative.len() - 1];
+        let pick = rng.gen_range(0.0, total);
+        let mut index = self.cumulative.iter().position(|sum| pick < *sum).unwrap_or(self.values.len() - 1);
+        // never end up in an empty cell because of rounding
+        while self.values[index] <= 0.0 && index > 0 {
+            index -= 1;
+        }
+        let cell_width = world_width / self.width as f32;
+        let cell_height = world_height / self.height as f32;
+        Location {
+            x: ((index % self.width) as f32 + rng.gen_range(0.0, 1.0)) * cell_width,
+            y: ((index / self.width) as f32 + rng.gen_range(0.0, 1.0)) * cell_height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_csv_grid() {
+        let map = DensityMap::from_csv("0, 1, 2\n3,4,5\n\n").unwrap();
+        assert_eq!(map.get_width(), 3);
+        assert_eq!(map.get_height(), 2);
+        assert_eq!(map.density_at(95.0, 10.0, 100.0, 100.0), 2.0);
+        assert_eq!(map.density_at(10.0, 95.0, 100.0, 100.0), 3.0);
+        assert!(!map.is_passable(10.0, 10.0, 100.0, 100.0));
+    }
+
+    #[test]
+    fn invalid_csv_is_rejected() {
+        assert!(DensityMap::from_csv("1,2\n3").is_err());
+        assert!(DensityMap::from_csv("1,a").is_err());
+        assert!(DensityMap::from_csv("0,0").is_err());
+        assert!(DensityMap::from_csv("1,-2").is_err());
+    }
+
+    #[test]
+    fn parse_plain_and_binary_pgm() {
+        let plain = DensityMap::from_pgm(b"P2\n# comment\n2 2\n255\n0 255\n51 0\n").unwrap();
+        let binary = DensityMap::from_pgm(b"P5 2 2 255\n\x00\xff\x33\x00").unwrap();
+        for map in [plain, binary].iter() {
+            assert_eq!(map.get_width(), 2);
+            assert_eq!(map.get_height(), 2);
+            assert_eq!(map.density_at(75.0, 25.0, 100.0, 100.0), 1.0);
+            assert_eq!(map.density_at(25.0, 75.0, 100.0, 100.0), 0.2);
+        }
+        assert!(DensityMap::from_pgm(b"P6 2 2 255\n").is_err());
+        // a header that doesn't match the pixels is an error, not a huge allocation
+        assert!(DensityMap::from_pgm(b"P2 100000000 100000000 255\n0 255\n").is_err());
+        assert!(DensityMap::from_pgm(format!("P5 {} 2 255\n\x00", usize::MAX).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn parse_rgba_pixels() {
+        let pixels = [0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 0, 51, 51, 51, 255];
+        let map = DensityMap::from_rgba(2, 2, &pixels).unwrap();
+        assert_eq!(map.density_at(25.0, 25.0, 100.0, 100.0), 0.0);
+        assert_eq!(map.density_at(75.0, 25.0, 100.0, 100.0), 1.0);
+        assert_eq!(map.density_at(25.0, 75.0, 100.0, 100.0), 0.0);
+        assert_eq!(map.density_at(75.0, 75.0, 100.0, 100.0), 0.2);
+        assert!(DensityMap::from_rgba(2, 2, &pixels[..12]).is_err());
+        assert!(DensityMap::from_rgba(usize::MAX / 2, 2, &pixels).is_err());
+    }
+
+    #[test]
+    fn steps_over_an_empty_strip_are_blocked() {
+        let map = DensityMap::from_csv("1,1,0,1,1").unwrap();
+        let world = Dimensions::new(100.0, 100.0);
+        let from = Location { x: 30.0, y: 50.0 };
+        assert!(map.is_step_passable(&from, &Location { x: 35.0, y: 50.0 }, &world));
+        assert!(!map.is_step_passable(&from, &Location { x: 65.0, y: 50.0 }, &world));
+        // going around the border of the world is the short way, which doesn't cross the strip
+        assert!(map.is_step_passable(&Location { x: 90.0, y: 50.0 }, &Location { x: 5.0, y: 50.0 }, &world));
+    }
+
+    #[test]
+    fn people_are_only_placed_in_populated_cells() {
+        let map = DensityMap::from_csv("0,1\n0,3").unwrap();
+        let mut bottom = 0;
+        for _ in 0..1000 {
+            let location = map.random_location(100.0, 100.0);
+            assert!(location.x >= 50.0 && location.x < 100.0);
+            if location.y >= 50.0 {
+                bottom += 1;
+            }
+        }
+        assert!(bottom > 650 && bottom < 850);
+    }
+}
@@ -0,0 +1,114 @@
+use super::person::Location;
+
+/// The size of the world. The world wraps around, leaving it at one side enters it at the other side.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Dimensions {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Dimensions {
+    pub fn new(width: f32, height: f32) -> Dimensions {
+        Dimensions { width, height }
+    }
+    /// The place in the world that `location` ends up at, `location` may lie outside of the world
+    pub fn wrap(&self, location: &Location) -> Location {
+        Location {
+            x: wrap(location.x, self.width),
+            y: wrap(location.y, self.height),
+        }
+    }
+    /// The shortest way from `from` to `to`, which may cross the border of the world
+    pub fn delta(&self, from: &Location, to: &Location) -> (f32, f32) {
+        (shortest(to.x - from.x, self.width), shortest(to.y - from.y, self.height))
+    }
+    pub fn sqr_distance(&self, from: &Location, to: &Location) -> f32 {
+        let (diff_x, diff_y) = self.delta(from, to);
+        diff_x * diff_x + diff_y * diff_y
+    }
+}
+
+// The coordinate in [0, size)
+fn wrap(value: f32, size: f32) -> f32 {
+    let wrapped = value.rem_euclid(size);
+    // rounding can end up exactly at the border for values just below 0
+    if wrapped < size {
+        wrapped
+    } else {
+        0.0
+    }
+}
+
+// The difference between two coordinates, going around the world when that is shorter
+fn shortest(diff: f32, size: f32) -> f32 {
+    let diff = wrap(diff, size);
+    if diff > size / 2.0 {
+        diff - size
+    } else {
+        diff
+    }
+}
+
+/// The world divided into `columns` x `rows` cells of the same size
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub dimensions: Dimensions,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Grid {
+    pub fn new(dimensions: Dimensions, columns: usize, rows: usize) -> Grid {
+        Grid {
+            dimensions,
+            columns: columns.max(1),
+            rows: rows.max(1),
+        }
+    }
+    /// Cells as large as `size`, with at most `max_cells` cells on each side
+    pub fn fit(dimensions: Dimensions, size: f32, max_cells: usize) -> Grid {
+        let cells = |length: f32| ((length / size).floor() as usize).clamp(1, max_cells);
+        Grid::new(dimensions, cells(dimensions.width), cells(dimensions.height))
+    }
+    pub fn cell_width(&self) -> f32 {
+        self.dimensions.width / self.columns as f32
+    }
+    pub fn cell_height(&self) -> f32 {
+        self.dimensions.height / self.rows as f32
+    }
+    pub fn cell_count(&self) -> usize {
+        self.columns * self.rows
+    }
+    /// The column and the row of the cell with the location (x, y)
+    pub fn cell(&self, x: f32, y: f32) -> (usize, usize) {
+        let position = self.dimensions.wrap(&Location { x, y });
+        let column = (position.x / self.cell_width()).floor() as usize;
+        let row = (position.y / self.cell_height()).floor() as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+    /// The index of the cell at `column` and `row`, which wrap around the world
+    pub fn index(&self, column: isize, row: isize) -> usize {
+        let column = column.rem_euclid(self.columns as isize) as usize;
+        let row = row.rem_euclid(self.rows as isize) as usize;
+        column * self.rows + row
+    }
+    pub fn index_of(&self, x: f32, y: f32) -> usize {
+        let (column, row) = self.cell(x, y);
+        column * self.rows + row
+    }
+    /// The columns with a place within `distance` of a place in `column`, every column once
+    pub fn columns_within(&self, column: usize, distance: f32) -> Vec<isize> {
+        Grid::within(column, (distance / self.cell_width()).ceil() as usize, self.columns)
+    }
+    /// The rows with a place within `distance` of a place in `row`, every row once
+    pub fn rows_within(&self, row: usize, distance: f32) -> Vec<isize> {
+        Grid::within(row, (distance / self.cell_height()).ceil() as usize, self.rows)
+    }
+    fn within(center: usize, range: usize, count: usize) -> Vec<isize> {
+        if 2 * range + 1 >= count {
+            (0..count as isize).collect()
+        } else {
+            (center as isize - range as isize..center as isize + range as isize + 1).collect()
+        }
+    }
+}
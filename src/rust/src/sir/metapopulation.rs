@@ -0,0 +1,229 @@
+use super::world::{Stats, World};
+use wasm_bindgen::prelude::*;
+
+/// A set of worlds (regions, e.g. cities) connected by a travel network.
+/// Each tick every region is updated and people travel between regions
+/// according to the travel matrix.
+#[wasm_bindgen]
+pub struct Metapopulation {
+    regions: Vec<World>,
+    // travel[from][to] is the number of people travelling per tick
+    travel: Vec<Vec<f32>>,
+    // fractional travellers that still have to leave
+    pending: Vec<Vec<f32>>,
+}
+
+#[wasm_bindgen]
+impl Metapopulation {
+    pub fn new() -> Metapopulation {
+        Metapopulation {
+            regions: Vec::new(),
+            travel: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+    /// Adds a region and returns its index, by default nobody travels to or from it
+    pub fn add_region(&mut self, world: World) -> usize {
+        self.regions.push(world);
+        for row in self.travel.iter_mut().chain(self.pending.iter_mut()) {
+            row.push(0.0);
+        }
+        self.travel.push(vec![0.0; self.regions.len()]);
+        self.pending.push(vec![0.0; self.regions.len()]);
+        self.regions.len() - 1
+    }
+    pub fn num_regions(&self) -> usize {
+        self.regions.len()
+    }
+    /// Sets the number of people moving from region `from` to region `to` every tick.
+    /// Fractions are accumulated, 0.1 means one person every 10 ticks.
+    pub fn set_travel(&mut self, from: usize, to: usize, people_per_tick: f32) {
+        self.travel[from][to] = people_per_tick.max(0.0);
+    }
+    pub fn get_travel(&self, from: usize, to: usize) -> f32 {
+        self.travel[from][to]
+    }
+    /// Stops all travel to and from a region
+    pub fn close_borders(&mut self, region: usize) {
+        for other in 0..self.regions.len() {
+            self.set_travel(region, other, 0.0);
+            self.set_travel(other, region, 0.0);
+            self.pending[region][other] = 0.0;
+            self.pending[other][region] = 0.0;
+        }
+    }
+    pub fn update(&mut self) {
+        for region in self.regions.iter_mut() {
+            region.update();
+        }
+        for from in 0..self.regions.len() {
+            for to in 0..self.regions.len() {
+                if from == to {
+                    continue;
+                }
+                self.pending[from][to] += self.travel[from][to];
+                while self.pending[from][to] >= 1.0 {
+                    self.pending[from][to] -= 1.0;
+                    let (origin, destination) = self.pair(from, to);
+                    if let Some(person) = origin.emigrate() {
+                        destination.immigrate(person, origin.strains());
+                    }
+                }
+            }
+        }
+    }
+    pub fn get_region_stats(&self, region: usize) -> Stats {
+        self.regions[region].get_stats()
+    }
+    /// Returns the stats over all regions together
+    pub fn get_stats(&self) -> Stats {
+        let mut total = 0.0;
+        let mut stats = Stats {
+            susceptable: 0.0,
+            exposed: 0.0,
+            infected: 0.0,
+            recovered: 0.0,
+            vaccinated: 0.0,
+            cases: 0.0,
+            detected: 0.0,
+            isolated: 0.0,
+            averted: 0.0,
+            incidence: 0.0,
+        };
+        for region in self.regions.iter() {
+            let size = region.population_size() as f32;
+            if size == 0.0 {
+                continue;
+            }
+            let region_stats = region.get_stats();
+            stats.susceptable += region_stats.susceptable * size;
+            stats.exposed += region_stats.exposed * size;
+            stats.infected += region_stats.infected * size;
+            stats.recovered += region_stats.recovered * size;
+            stats.vaccinated += region_stats.vaccinated * size;
+            stats.cases += region_stats.cases * size;
+            stats.detected += region_stats.detected * size;
+            stats.isolated += region_stats.isolated * size;
+            stats.averted += region_stats.averted * size;
+            stats.incidence += region_stats.incidence * size;
+            total += size;
+        }
+        if total > 0.0 {
+            stats.susceptable /= total;
+            stats.exposed /= total;
+            stats.infected /= total;
+            stats.recovered /= total;
+            stats.vaccinated /= total;
+            stats.cases /= total;
+            stats.detected /= total;
+            stats.isolated /= total;
+            stats.averted /= total;
+            stats.incidence /= total;
+        }
+        stats
+    }
+    pub fn render_region(&self, region: usize, canvas_id: &str) {
+        self.regions[region].render(canvas_id);
+    }
+}
+
+impl Metapopulation {
+    // The regions `from` and `to`, which are different regions
+    fn pair(&mut self, from: usize, to: usize) -> (&mut World, &mut World) {
+        if from < to {
+            let (first, second) = self.regions.split_at_mut(to);
+            (&mut first[from], &mut second[0])
+        } else {
+            let (first, second) = self.regions.split_at_mut(from);
+            (&mut second[0], &mut first[to])
+        }
+    }
+    pub fn region(&self, region: usize) -> &World {
+        &self.regions[region]
+    }
+}
+
+impl Default for Metapopulation {
+    fn default() -> Self {
+        Metapopulation::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sir::person::PersonState;
+    use crate::sir::seeding::Seeding;
+    use crate::sir::virus::Virus;
+    use crate::sir::world::PopulationDistribution;
+
+    fn two_regions() -> Metapopulation {
+        let mut metapopulation = Metapopulation::new();
+        for _ in 0..2 {
+            let mut world = World::new(50, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+            world.config(0.0);
+            metapopulation.add_region(world);
+        }
+        metapopulation
+    }
+
+    #[test]
+    fn people_travel_between_regions() {
+        let mut metapopulation = two_regions();
+        metapopulation.set_travel(0, 1, 2.5);
+        for _ in 0..4 {
+            metapopulation.update();
+        }
+        assert_eq!(metapopulation.region(0).population_size(), 40);
+        assert_eq!(metapopulation.region(1).population_size(), 60);
+        let mut ids: Vec<usize> = metapopulation.region(1).people().map(|person| person.get_id()).collect();
+        ids.sort();
+        assert_eq!(ids, (0..60).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn travellers_keep_the_virus_they_carry() {
+        let mut metapopulation = Metapopulation::new();
+        for distance in [10.0, 20.0].iter() {
+            let mut virus = Virus::corona();
+            virus.distance = *distance;
+            let mut world = World::new(20, 100.0, 100.0, virus, PopulationDistribution::Random);
+            world.seed(&Seeding::fractions(20, if *distance < 15.0 { 1.0 } else { 0.0 }, 0.0, 0.0));
+            metapopulation.add_region(world);
+        }
+        metapopulation.set_travel(0, 1, 3.0);
+        metapopulation.update();
+        let region = metapopulation.region(1);
+        assert_eq!(region.strains().len(), 2);
+        let carried: Vec<f32> = region
+            .people()
+            .filter_map(|person| match person.get_state() {
+                PersonState::Infectious(strain) => Some(region.strains().get(strain).distance),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(carried, vec![10.0; 3]);
+    }
+
+    #[test]
+    fn closed_borders_stop_travel() {
+        let mut metapopulation = two_regions();
+        metapopulation.set_travel(0, 1, 1.0);
+        metapopulation.set_travel(1, 0, 3.0);
+        metapopulation.close_borders(1);
+        metapopulation.update();
+        assert_eq!(metapopulation.region(0).population_size(), 50);
+        assert_eq!(metapopulation.region(1).population_size(), 50);
+    }
+
+    #[test]
+    fn global_stats_are_weighted_by_region_size() {
+        let mut metapopulation = two_regions();
+        let world = World::new(100, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        metapopulation.add_region(world);
+        let stats = metapopulation.get_stats();
+        let total = stats.susceptable + stats.exposed + stats.infected + stats.recovered;
+        assert!((total - 1.0).abs() < 0.0001);
+        assert!(stats.infected <= 3.0 / 200.0);
+    }
+}
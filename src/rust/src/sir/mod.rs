@@ -2,3 +2,15 @@ pub mod person;
 pub mod virus;
 pub mod world;
 pub mod population;
+pub mod counts;
+pub mod wall;
+pub mod metapopulation;
+pub mod density;
+pub mod geometry;
+pub mod neighbours;
+pub mod seeding;
+pub mod policy;
+pub mod vaccination;
+pub mod testing;
+pub mod tracing;
+pub mod demographics;
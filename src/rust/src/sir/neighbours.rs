@@ -0,0 +1,369 @@
+use super::geometry::{Dimensions, Grid};
+use super::person::Location;
+use std::fmt::Debug;
+use wasm_bindgen::prelude::*;
+
+const MAX_CELLS: usize = 1000;
+// Ranges of the k-d tree with this many people or less are searched one by one
+const LEAF_SIZE: usize = 8;
+
+/// The ways to find the people close to a location
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpatialIndex {
+    /// Cells of the same size, fast when people are spread over the world
+    Grid,
+    /// A k-d tree, keeps up when many people crowd together
+    KdTree,
+}
+
+impl SpatialIndex {
+    pub fn create(&self, world: Dimensions) -> Box<dyn NeighbourIndex> {
+        match self {
+            SpatialIndex::Grid => Box::new(GridIndex::new(Grid::new(world, 1, 1))),
+            SpatialIndex::KdTree => Box::new(KdTree::new(world)),
+        }
+    }
+}
+
+/// Finds the people close to a location. The index is built from the positions of everybody
+/// at once and knows each person by its place in those positions.
+pub trait NeighbourIndex: Debug + Send + Sync {
+    fn kind(&self) -> SpatialIndex;
+    /// Tunes the index for searches within `distance`, returns whether it has to be built again
+    fn fit(&mut self, _distance: f32) -> bool {
+        false
+    }
+    /// Forgets everybody and indexes the people at `positions`
+    fn build(&mut self, positions: &[Location]);
+    /// Calls `found` with the place in the positions and the position of everybody within `radius` of `center`
+    fn within(&self, center: &Location, radius: f32, found: &mut dyn FnMut(usize, &Location));
+    /// The places in the positions of everybody, people close to each other come one after the other
+    fn order(&self) -> &[usize];
+    fn clone_box(&self) -> Box<dyn NeighbourIndex>;
+}
+
+impl Clone for Box<dyn NeighbourIndex> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Everybody sorted by cell with a counting sort. The positions are copied into flat arrays
+/// in the same order, so looking through a cell only reads the positions of its people.
+#[derive(Clone, Debug)]
+pub struct GridIndex {
+    grid: Grid,
+    // the people of cell `c` are at `starts[c]..starts[c + 1]`
+    starts: Vec<usize>,
+    order: Vec<usize>,
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+}
+
+impl GridIndex {
+    pub fn new(grid: Grid) -> GridIndex {
+        GridIndex {
+            grid,
+            starts: vec![0; grid.cell_count() + 1],
+            order: Vec::new(),
+            xs: Vec::new(),
+            ys: Vec::new(),
+        }
+    }
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+    // The people in the cell at `column` and `row`, which wrap around the world
+    fn cell(&self, column: isize, row: isize) -> impl Iterator<Item = (usize, Location)> + '_ {
+        let cell = self.grid.index(column, row);
+        (self.starts[cell]..self.starts[cell + 1]).map(move |rank| (self.order[rank], Location { x: self.xs[rank], y: self.ys[rank] }))
+    }
+}
+
+impl NeighbourIndex for GridIndex {
+    fn kind(&self) -> SpatialIndex {
+        SpatialIndex::Grid
+    }
+    /// Makes the cells as large as `distance`, with at most 1000 cells on each side.
+    /// Searches work with any size, this only changes how fast they are.
+    fn fit(&mut self, distance: f32) -> bool {
+        let grid = Grid::fit(self.grid.dimensions, distance, MAX_CELLS);
+        let changed = grid != self.grid;
+        self.grid = grid;
+        changed
+    }
+    fn build(&mut self, positions: &[Location]) {
+        let cells: Vec<usize> = positions.iter().map(|position| self.grid.index_of(position.x, position.y)).collect();
+        self.starts.clear();
+        self.starts.resize(self.grid.cell_count() + 1, 0);
+        for cell in cells.iter() {
+            self.starts[cell + 1] += 1;
+        }
+        for cell in 1..self.starts.len() {
+            self.starts[cell] += self.starts[cell - 1];
+        }
+        let mut next = self.starts.clone();
+        self.order.resize(cells.len(), 0);
+        self.xs.resize(cells.len(), 0.0);
+        self.ys.resize(cells.len(), 0.0);
+        for (person, cell) in cells.into_iter().enumerate() {
+            let rank = next[cell];
+            next[cell] += 1;
+            self.order[rank] = person;
+            self.xs[rank] = positions[person].x;
+            self.ys[rank] = positions[person].y;
+        }
+    }
+    fn within(&self, center: &Location, radius: f32, found: &mut dyn FnMut(usize, &Location)) {
+        let world = &self.grid.dimensions;
+        let (column, row) = self.grid.cell(center.x, center.y);
+        for x in self.grid.columns_within(column, radius) {
+            for y in self.grid.rows_within(row, radius) {
+                for (person, position) in self.cell(x, y) {
+                    if world.sqr_distance(center, &position) < radius * radius {
+                        found(person, &position);
+                    }
+                }
+            }
+        }
+    }
+    fn order(&self) -> &[usize] {
+        &self.order
+    }
+    fn clone_box(&self) -> Box<dyn NeighbourIndex> {
+        Box::new(self.clone())
+    }
+}
+
+/// A k-d tree over everybody, the median of every range along x or y splits it in two halves
+#[derive(Clone, Debug)]
+pub struct KdTree {
+    world: Dimensions,
+    nodes: Vec<(usize, Location)>,
+    order: Vec<usize>,
+}
+
+impl KdTree {
+    pub fn new(world: Dimensions) -> KdTree {
+        KdTree { world, nodes: Vec::new(), order: Vec::new() }
+    }
+    fn coordinate(location: &Location, depth: usize) -> f32 {
+        match depth % 2 {
+            0 => location.x,
+            _ => location.y,
+        }
+    }
+    fn split(nodes: &mut [(usize, Location)], depth: usize) {
+        if nodes.len() <= LEAF_SIZE {
+            return;
+        }
+        let middle = nodes.len() / 2;
+        nodes.select_nth_unstable_by(middle, |(_, a), (_, b)| {
+            KdTree::coordinate(a, depth).partial_cmp(&KdTree::coordinate(b, depth)).unwrap()
+        });
+        let (lower, upper) = nodes.split_at_mut(middle);
+        KdTree::split(lower, depth + 1);
+        KdTree::split(&mut upper[1..], depth + 1);
+    }
+    // The parts of [center - radius, center + radius] inside [0, size), the range wraps around the world
+    fn ranges(center: f32, radius: f32, size: f32) -> Vec<(f32, f32)> {
+        if 2.0 * radius >= size {
+            vec![(0.0, size)]
+        } else if center - radius < 0.0 {
+            vec![(0.0, center + radius), (center - radius + size, size)]
+        } else if center + radius > size {
+            vec![(center - radius, size), (0.0, center + radius - size)]
+        } else {
+            vec![(center - radius, center + radius)]
+        }
+    }
+    // Only the people inside `bounds` count, so nobody is found twice when the search wraps around the world
+    fn check(
+        &self,
+        person: usize,
+        position: &Location,
+        bounds: &[(f32, f32); 2],
+        center: &Location,
+        radius: f32,
+        found: &mut dyn FnMut(usize, &Location),
+    ) {
+        let inside = |value: f32, (low, high): (f32, f32)| low <= value && value <= high;
+        if inside(position.x, bounds[0])
+            && inside(position.y, bounds[1])
+            && self.world.sqr_distance(center, position) < radius * radius
+        {
+            found(person, position);
+        }
+    }
+    // Looks through the part of the tree in `nodes`, `bounds` are the ranges along x and y that can hold a match
+    fn search(
+        &self,
+        nodes: &[(usize, Location)],
+        depth: usize,
+        bounds: &[(f32, f32); 2],
+        center: &Location,
+        radius: f32,
+        found: &mut dyn FnMut(usize, &Location),
+    ) {
+        if nodes.len() <= LEAF_SIZE {
+            for (person, position) in nodes.iter() {
+                self.check(*person, position, bounds, center, radius, found);
+            }
+            return;
+        }
+        let middle = nodes.len() / 2;
+        let (person, position) = &nodes[middle];
+        self.check(*person, position, bounds, center, radius, found);
+        let value = KdTree::coordinate(position, depth);
+        let (low, high) = bounds[depth % 2];
+        if low <= value {
+            self.search(&nodes[..middle], depth + 1, bounds, center, radius, found);
+        }
+        if value <= high {
+            self.search(&nodes[middle + 1..], depth + 1, bounds, center, radius, found);
+        }
+    }
+}
+
+impl NeighbourIndex for KdTree {
+    fn kind(&self) -> SpatialIndex {
+        SpatialIndex::KdTree
+    }
+    fn build(&mut self, positions: &[Location]) {
+        self.nodes = positions.iter().map(|position| self.world.wrap(position)).enumerate().collect();
+        KdTree::split(&mut self.nodes, 0);
+        self.order = self.nodes.iter().map(|(person, _)| *person).collect();
+    }
+    fn within(&self, center: &Location, radius: f32, found: &mut dyn FnMut(usize, &Location)) {
+        let center = self.world.wrap(center);
+        for x_range in KdTree::ranges(center.x, radius, self.world.width) {
+            for y_range in KdTree::ranges(center.y, radius, self.world.height) {
+                self.search(&self.nodes, 0, &[x_range, y_range], &center, radius, found);
+            }
+        }
+    }
+    fn order(&self) -> &[usize] {
+        &self.order
+    }
+    fn clone_box(&self) -> Box<dyn NeighbourIndex> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn within(index: &dyn NeighbourIndex, center: &Location, radius: f32) -> Vec<usize> {
+        let mut people = Vec::new();
+        index.within(center, radius, &mut |person, _| people.push(person));
+        people.sort();
+        people
+    }
+
+    #[test]
+    fn correct_amount_of_boxes_is_made() {
+        let index = GridIndex::new(Grid::new(Dimensions::new(100.0, 100.0), 10, 5));
+        assert_eq!(index.grid().columns, 10);
+        assert_eq!(index.grid().rows, 5);
+        assert_eq!(index.starts.len(), 51);
+    }
+
+    #[test]
+    fn people_are_added_to_correct_box() {
+        let mut index = GridIndex::new(Grid::new(Dimensions::new(100.0, 100.0), 10, 10));
+        index.build(&[Location { x: 12.0, y: 23.0 }]);
+        assert_eq!(index.cell(1, 2).count(), 1);
+    }
+
+    #[test]
+    fn everybody_is_in_the_correct_box() {
+        let mut index = GridIndex::new(Grid::new(Dimensions::new(100.0, 100.0), 10, 5));
+        let positions: Vec<Location> = (0..100).map(|index| Location { x: index as f32, y: (index * 7 % 100) as f32 }).collect();
+        index.build(&positions);
+        let mut count = 0;
+        for column in 0..10 {
+            for row in 0..5 {
+                for (person, position) in index.cell(column, row) {
+                    assert_eq!(position, positions[person]);
+                    assert_eq!(index.grid().cell(position.x, position.y), (column as usize, row as usize));
+                    count += 1;
+                }
+            }
+        }
+        assert_eq!(count, 100);
+    }
+
+    #[test]
+    fn get_iterator_of_correct_box_with_wrapping() {
+        // We make a grid of 2x2 and put a single person in each
+        let mut index = GridIndex::new(Grid::new(Dimensions::new(10.0, 10.0), 2, 2));
+        let positions = [(2.0, 2.0), (7.0, 2.0), (2.0, 7.0), (7.0, 7.0)];
+        index.build(&positions.iter().map(|(x, y)| Location { x: *x, y: *y }).collect::<Vec<Location>>());
+        assert_eq!(index.cell(0, 0).next().unwrap().0, 0);
+        assert_eq!(index.cell(1, 0).next().unwrap().0, 1);
+        assert_eq!(index.cell(0, 1).next().unwrap().0, 2);
+        assert_eq!(index.cell(1, 1).next().unwrap().0, 3);
+        assert_eq!(index.cell(-1, -1).next().unwrap().0, 3);
+        assert_eq!(index.cell(3, 3).next().unwrap().0, 3);
+    }
+
+    #[test]
+    fn cells_fit_the_distance() {
+        let mut index = GridIndex::new(Grid::new(Dimensions::new(200.0, 100.0), 1, 1));
+        assert!(index.fit(10.0));
+        assert_eq!((index.grid().columns, index.grid().rows), (20, 10));
+        assert!(!index.fit(10.0));
+        index.fit(0.01);
+        assert_eq!((index.grid().columns, index.grid().rows), (MAX_CELLS, MAX_CELLS));
+        index.fit(500.0);
+        assert_eq!((index.grid().columns, index.grid().rows), (1, 1));
+    }
+
+    #[test]
+    fn every_index_finds_the_same_people() {
+        let world = Dimensions::new(300.0, 100.0);
+        let mut rng = rand::thread_rng();
+        // a crowd in a corner, so the tree and the searches wrap around the world
+        let positions: Vec<Location> = (0..1000)
+            .map(|index| {
+                if index % 2 == 0 {
+                    Location { x: rng.gen_range(-5.0, 5.0f32).rem_euclid(300.0), y: rng.gen_range(-5.0, 5.0f32).rem_euclid(100.0) }
+                } else {
+                    Location { x: rng.gen_range(0.0, 300.0), y: rng.gen_range(0.0, 100.0) }
+                }
+            })
+            .collect();
+        let mut grid = SpatialIndex::Grid.create(world);
+        grid.fit(10.0);
+        grid.build(&positions);
+        let mut tree = SpatialIndex::KdTree.create(world);
+        tree.build(&positions);
+        for index in [&grid, &tree].iter() {
+            let mut order = index.order().to_vec();
+            order.sort();
+            assert_eq!(order, (0..positions.len()).collect::<Vec<usize>>());
+        }
+        for radius in [0.5, 3.0, 10.0, 40.0, 120.0, 500.0].iter() {
+            for _ in 0..20 {
+                let center = Location { x: rng.gen_range(-10.0, 310.0), y: rng.gen_range(-10.0, 110.0) };
+                let expected: Vec<usize> = (0..positions.len())
+                    .filter(|person| world.sqr_distance(&center, &positions[*person]) < radius * radius)
+                    .collect();
+                assert_eq!(within(grid.as_ref(), &center, *radius), expected);
+                assert_eq!(within(tree.as_ref(), &center, *radius), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn the_tree_handles_everybody_at_the_same_place() {
+        let positions = vec![Location { x: 50.0, y: 50.0 }; 100];
+        let mut tree = KdTree::new(Dimensions::new(100.0, 100.0));
+        tree.build(&positions);
+        assert_eq!(within(&tree, &Location { x: 50.0, y: 50.0 }, 0.1).len(), 100);
+        assert!(within(&tree, &Location { x: 52.0, y: 50.0 }, 1.0).is_empty());
+    }
+}
@@ -1,25 +1,65 @@
-use super::virus::Virus;
+use super::geometry::Dimensions;
+use super::virus::{Strain, Strains, Virus};
+use super::wall::{is_blocked, Wall};
 use rand::Rng;
 
-#[derive(Debug, PartialEq, Clone)]
+/// The strain in `Exposed` and `Infectious` is the virus the person carries, see `Strains`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PersonState {
     Susceptible,
-    Infectious(Virus),
+    /// Infected, but not yet infectious
+    Exposed(Strain),
+    Infectious(Strain),
     Recovered(bool),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Location {
     pub x: f32,
     pub y: f32,
 }
 
+impl Location {
+    // We need the world to know its size because the world is circular
+    pub fn sqr_distance(&self, other: &Location, world: &Dimensions) -> f32 {
+        world.sqr_distance(self, other)
+    }
+}
+
+/// What changed about a person while ageing a tick, see `Person::update_age`
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Aged {
+    /// The state before, when the state changed
+    pub changed_from: Option<PersonState>,
+    /// The isolation of the person ended
+    pub released: bool,
+}
+
+/// The protection a vaccine gives, as the fraction of infections and deaths it prevents
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Protection {
+    pub infection: f32,
+    pub death: f32,
+}
+
 #[derive(Debug, Clone)]
 pub struct Person {
     id: usize,
     state: PersonState,
     infected_date: usize,
     pub age: usize,
+    /// Age of the person in years, `age` counts the ticks of the simulation
+    pub years: u32,
+    // between 0 and 1, people only follow policies with a compliance above their reluctance
+    reluctance: f32,
+    vaccine: Option<Protection>,
+    // the person stays in isolation as long as the age is below this value
+    isolated_until: usize,
+    detected: bool,
+    // the person got infected at some point, whatever the state is now
+    infected: bool,
+    // an infection of this person that a quarantine averted was counted already
+    averted: bool,
     pub position: Location,
     home: Location
 }
@@ -33,82 +73,440 @@ impl Person {
     }
     pub fn new(x: f32, y: f32, id: usize) -> Person {
         let position = Location { x, y };
+        let mut rng = rand::thread_rng();
         Person {
             id,
             state: PersonState::Susceptible,
             age: 0,
+            years: rng.gen_range(0, 90),
             infected_date: 0,
+            reluctance: rng.gen_range(0.0, 1.0),
+            vaccine: None,
+            isolated_until: 0,
+            detected: false,
+            infected: false,
+            averted: false,
             home: position.clone(),
             position,
         }
     }
     pub fn get_state(&self) -> PersonState {
-        self.state.clone()
+        self.state
     }
     pub fn get_id(&self) -> usize {
         self.id
     }
-    pub fn infect(&mut self, virus: Virus) {
+    pub fn get_reluctance(&self) -> f32 {
+        self.reluctance
+    }
+    pub fn vaccinate(&mut self, protection: Protection) {
+        self.vaccine = Some(protection);
+    }
+    pub fn is_vaccinated(&self) -> bool {
+        self.vaccine.is_some()
+    }
+    fn protection(&self) -> Protection {
+        protection(self.vaccine)
+    }
+    /// Isolated people stay where they are and don't infect anybody
+    pub fn isolate(&mut self, period: usize) {
+        self.isolated_until = self.isolated_until.max(self.age + period);
+    }
+    pub fn is_isolated(&self) -> bool {
+        self.age < self.isolated_until
+    }
+    /// Marks the person as a confirmed case
+    pub fn detect(&mut self) {
+        self.detected = true;
+    }
+    pub fn is_detected(&self) -> bool {
+        self.detected
+    }
+    /// Whether the person ever got infected, the true cases behind the detected ones
+    pub fn was_infected(&self) -> bool {
+        self.infected
+    }
+    /// Isolated without being a confirmed case, e.g. a traced contact
+    pub fn is_quarantined(&self) -> bool {
+        self.is_isolated() && !self.detected
+    }
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+    pub fn get_home(&self) -> &Location {
+        &self.home
+    }
+    /// Moves the person to a new place, which also becomes the new home
+    pub fn relocate(&mut self, x: f32, y: f32) {
+        self.position = Location { x, y };
+        self.home = self.position.clone();
+    }
+    /// Sets the state without any chance involved, e.g. to seed an outbreak.
+    /// Susceptible people never got infected, recovered people keep their past.
+    pub fn set_state(&mut self, state: PersonState) {
+        match state {
+            PersonState::Susceptible => self.infected = false,
+            PersonState::Exposed(_) | PersonState::Infectious(_) => self.infected = true,
+            PersonState::Recovered(_) => {}
+        }
+        self.state = state;
+        self.infected_date = self.age;
+        self.averted = false;
+    }
+    /// Replaces the strain this person carries, e.g. when the person moves to a world with other strains
+    pub fn set_strain(&mut self, strain: Strain) {
+        if let PersonState::Exposed(carried) | PersonState::Infectious(carried) = &mut self.state {
+            *carried = strain;
+        }
+    }
+    /// The chance that a contact with `virus` infects this person
+    pub fn infection_chance(&self, virus: &Virus) -> f32 {
+        if self.state == PersonState::Susceptible {
+            virus.infection_rate * (1.0 - self.protection().infection)
+        } else {
+            0.0
+        }
+    }
+    /// Counts an infection of this person that a quarantine averted. Returns the chance that `virus`
+    /// would have infected the person, or 0 when an averted infection was counted already.
+    pub fn avert(&mut self, virus: &Virus) -> f32 {
+        if self.averted {
+            return 0.0;
+        }
+        let chance = self.infection_chance(virus);
+        self.averted = chance > 0.0;
+        chance
+    }
+    /// Infects the person with `strain` by chance, `virus` is the virus of the strain, which policies
+    /// may have made less infectious. Returns whether the person got infected.
+    pub fn infect(&mut self, strain: Strain, virus: &Virus) -> bool {
         if self.state == PersonState::Susceptible {
             let mut rng = rand::thread_rng();
             let chance = rng.gen_range(0.0, 1.0);
-            if chance <= virus.infection_rate {
-                self.state = PersonState::Infectious(virus);
+            if chance <= self.infection_chance(virus) {
+                if virus.incubation_time > 0 {
+                    self.state = PersonState::Exposed(strain);
+                } else {
+                    self.state = PersonState::Infectious(strain);
+                }
                 self.infected_date = self.age;
+                self.infected = true;
+                self.averted = false;
+                return true;
             }
         }
+        false
     }
-    pub fn update_age(&mut self) {
-        self.age += 1;
-        if let PersonState::Infectious(virus) = &self.state {
-            if self.infected_date + virus.recovery_time < self.age
-            {
-                let mut rng = rand::thread_rng();
-                let chance = rng.gen_range(0.0, 1.0);
-                self.state = PersonState::Recovered(chance < virus.mortality_rate);
-            }
-        }
+    /// Returns what changed, e.g. from infectious to recovered or the end of an isolation.
+    /// `strains` holds the virus the person carries.
+    pub fn update_age(&mut self, strains: &Strains) -> Aged {
+        self.update_age_with(&mut rand::thread_rng(), strains)
     }
-    pub fn move_random(&mut self, max_speed: f32, max_x: f32, max_y: f32) {
-        if let PersonState::Recovered(is_dead) = self.state {
-            if is_dead {
-                return ()
-            }
+    /// Like `update_age`, with the random numbers coming from `rng`
+    pub fn update_age_with<R: Rng>(&mut self, rng: &mut R, strains: &Strains) -> Aged {
+        let protection = self.protection();
+        grow_older(rng, strains, &mut self.state, &mut self.infected_date, &mut self.age, self.isolated_until, protection)
+    }
+    pub fn move_random(&mut self, max_speed: f32, world: &Dimensions, walls: &[Wall]) {
+        self.move_random_with(&mut rand::thread_rng(), max_speed, world, walls);
+    }
+    /// Like `move_random`, with the random numbers coming from `rng`
+    pub fn move_random_with<R: Rng>(&mut self, rng: &mut R, max_speed: f32, world: &Dimensions, walls: &[Wall]) {
+        let isolated = self.is_isolated();
+        walk(rng, self.state, isolated, &mut self.position, max_speed, world, walls);
+    }
+    // We need the world to know its size because the world is circular
+    pub fn sqr_distance(&self, other: &Person, world: &Dimensions) -> f32 {
+        self.position.sqr_distance(&other.position, world)
+    }
+}
+
+fn protection(vaccine: Option<Protection>) -> Protection {
+    vaccine.unwrap_or(Protection { infection: 0.0, death: 0.0 })
+}
+
+// Ages a person a tick. `Person` and `People` keep the fields of a person in different places,
+// so they are passed one by one.
+fn grow_older<R: Rng>(
+    rng: &mut R,
+    strains: &Strains,
+    state: &mut PersonState,
+    infected_date: &mut usize,
+    age: &mut usize,
+    isolated_until: usize,
+    protection: Protection,
+) -> Aged {
+    *age += 1;
+    let changed_from = match *state {
+        PersonState::Exposed(strain) if *infected_date + strains.get(strain).incubation_time < *age => {
+            *state = PersonState::Infectious(strain);
+            *infected_date = *age;
+            Some(PersonState::Exposed(strain))
+        }
+        PersonState::Infectious(strain) if *infected_date + strains.get(strain).recovery_time < *age => {
+            let virus = strains.get(strain);
+            let chance = rng.gen_range(0.0, 1.0);
+            *state = PersonState::Recovered(chance < virus.mortality_rate * (1.0 - protection.death));
+            Some(PersonState::Infectious(strain))
         }
-        // dont do anything if speed is 0
-        if max_speed < 0.00001 {
+        _ => None,
+    };
+    Aged { changed_from, released: *age == isolated_until }
+}
+
+// Moves a person from `position` by a random step of at most `max_speed` in each direction
+fn walk<R: Rng>(
+    rng: &mut R,
+    state: PersonState,
+    isolated: bool,
+    position: &mut Location,
+    max_speed: f32,
+    world: &Dimensions,
+    walls: &[Wall],
+) {
+    if let PersonState::Recovered(is_dead) = state {
+        if is_dead {
             return ()
         }
-        let x = self.position.x;
-        let y = self.position.y;
-        let mut rng = rand::thread_rng();
-        let mut new_x = x + rng.gen_range(-max_speed, max_speed);
-        let mut new_y = y + rng.gen_range(-max_speed, max_speed);
-        if new_x >= max_x {
-            new_x = 0.0;
-        };
-        if new_y >= max_y {
-            new_y = 0.0;
+    }
+    // dont do anything if speed is 0 or when in isolation
+    if max_speed < 0.00001 || isolated {
+        return ()
+    }
+    let diff_x = rng.gen_range(-max_speed, max_speed);
+    let diff_y = rng.gen_range(-max_speed, max_speed);
+    let target = Location { x: position.x + diff_x, y: position.y + diff_y };
+    // people bump into walls, they stay where they are this turn
+    if is_blocked(walls, position, &target) {
+        return;
+    }
+    // when wrapping around, also check the part of the path at the other side of the world
+    let wrapped_to = world.wrap(&target);
+    let wrapped_from = Location { x: wrapped_to.x - diff_x, y: wrapped_to.y - diff_y };
+    if wrapped_to != target && is_blocked(walls, &wrapped_from, &wrapped_to) {
+        return;
+    }
+    *position = wrapped_to;
+}
+
+/// Many people, every field of `Person` is kept in an array of its own.
+/// The updates of every tick only go through the arrays they need.
+#[derive(Clone, Debug, Default)]
+pub struct People {
+    ids: Vec<usize>,
+    states: Vec<PersonState>,
+    infected_dates: Vec<usize>,
+    ages: Vec<usize>,
+    years: Vec<u32>,
+    reluctances: Vec<f32>,
+    vaccines: Vec<Option<Protection>>,
+    isolated_until: Vec<usize>,
+    detected: Vec<bool>,
+    infected: Vec<bool>,
+    averted: Vec<bool>,
+    positions: Vec<Location>,
+    homes: Vec<Location>,
+}
+
+impl People {
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+    pub fn push(&mut self, person: Person) {
+        self.ids.push(person.id);
+        self.states.push(person.state);
+        self.infected_dates.push(person.infected_date);
+        self.ages.push(person.age);
+        self.years.push(person.years);
+        self.reluctances.push(person.reluctance);
+        self.vaccines.push(person.vaccine);
+        self.isolated_until.push(person.isolated_until);
+        self.detected.push(person.detected);
+        self.infected.push(person.infected);
+        self.averted.push(person.averted);
+        self.positions.push(person.position);
+        self.homes.push(person.home);
+    }
+    /// A copy of the person at `index`
+    pub fn get(&self, index: usize) -> Person {
+        Person {
+            id: self.ids[index],
+            state: self.states[index],
+            infected_date: self.infected_dates[index],
+            age: self.ages[index],
+            years: self.years[index],
+            reluctance: self.reluctances[index],
+            vaccine: self.vaccines[index],
+            isolated_until: self.isolated_until[index],
+            detected: self.detected[index],
+            infected: self.infected[index],
+            averted: self.averted[index],
+            position: self.positions[index].clone(),
+            home: self.homes[index].clone(),
         }
-        if new_x < 0.0 {
-            new_x = max_x - 1.0;
-        };
-        if new_y < 0.0 {
-            new_y = max_y - 1.0;
+    }
+    /// Replaces the person at `index` by `person`
+    pub fn set(&mut self, index: usize, person: &Person) {
+        self.ids[index] = person.id;
+        self.states[index] = person.state;
+        self.infected_dates[index] = person.infected_date;
+        self.ages[index] = person.age;
+        self.years[index] = person.years;
+        self.reluctances[index] = person.reluctance;
+        self.vaccines[index] = person.vaccine;
+        self.isolated_until[index] = person.isolated_until;
+        self.detected[index] = person.detected;
+        self.infected[index] = person.infected;
+        self.averted[index] = person.averted;
+        self.positions[index] = person.position.clone();
+        self.homes[index] = person.home.clone();
+    }
+    /// Removes the person at `index`, the last person takes its place
+    pub fn swap_remove(&mut self, index: usize) -> Person {
+        let person = self.get(index);
+        self.ids.swap_remove(index);
+        self.states.swap_remove(index);
+        self.infected_dates.swap_remove(index);
+        self.ages.swap_remove(index);
+        self.years.swap_remove(index);
+        self.reluctances.swap_remove(index);
+        self.vaccines.swap_remove(index);
+        self.isolated_until.swap_remove(index);
+        self.detected.swap_remove(index);
+        self.infected.swap_remove(index);
+        self.averted.swap_remove(index);
+        self.positions.swap_remove(index);
+        self.homes.swap_remove(index);
+        person
+    }
+    pub fn id(&self, index: usize) -> usize {
+        self.ids[index]
+    }
+    pub fn state(&self, index: usize) -> PersonState {
+        self.states[index]
+    }
+    pub fn reluctance(&self, index: usize) -> f32 {
+        self.reluctances[index]
+    }
+    /// See `Person::is_isolated`
+    pub fn is_isolated(&self, index: usize) -> bool {
+        self.ages[index] < self.isolated_until[index]
+    }
+    pub fn is_detected(&self, index: usize) -> bool {
+        self.detected[index]
+    }
+    pub fn position(&self, index: usize) -> &Location {
+        &self.positions[index]
+    }
+    pub fn positions(&self) -> &[Location] {
+        &self.positions
+    }
+    /// The arrays that change while everybody moves and ages
+    pub fn moving(&mut self) -> Moving<'_> {
+        Moving {
+            ids: &self.ids,
+            reluctances: &self.reluctances,
+            vaccines: &self.vaccines,
+            isolated_until: &self.isolated_until,
+            states: &mut self.states,
+            infected_dates: &mut self.infected_dates,
+            ages: &mut self.ages,
+            positions: &mut self.positions,
+        }
+    }
+}
+
+/// The arrays of `People` that change while people move and age, for a range of people
+pub struct Moving<'a> {
+    ids: &'a [usize],
+    reluctances: &'a [f32],
+    vaccines: &'a [Option<Protection>],
+    isolated_until: &'a [usize],
+    states: &'a mut [PersonState],
+    infected_dates: &'a mut [usize],
+    ages: &'a mut [usize],
+    positions: &'a mut [Location],
+}
+
+impl<'a> Moving<'a> {
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+    /// Splits the people in parts of `size` people, the last part may be smaller
+    pub fn split(self, size: usize) -> Vec<Moving<'a>> {
+        let mut parts = Vec::new();
+        let mut rest = self;
+        while rest.len() > size {
+            let (part, others) = rest.split_at(size);
+            parts.push(part);
+            rest = others;
         }
-        self.position.x = new_x;
-        self.position.y = new_y;
+        parts.push(rest);
+        parts
     }
-    fn min_diff(x1: f32, x2: f32, width: f32) -> f32 {
-        let diff_1 = (x1 - x2).abs();
-        let diff_2 = (diff_1 - width).abs();
-        diff_1.min(diff_2)
+    fn split_at(self, middle: usize) -> (Moving<'a>, Moving<'a>) {
+        let (ids, other_ids) = self.ids.split_at(middle);
+        let (reluctances, other_reluctances) = self.reluctances.split_at(middle);
+        let (vaccines, other_vaccines) = self.vaccines.split_at(middle);
+        let (isolated_until, other_isolated_until) = self.isolated_until.split_at(middle);
+        let (states, other_states) = self.states.split_at_mut(middle);
+        let (infected_dates, other_infected_dates) = self.infected_dates.split_at_mut(middle);
+        let (ages, other_ages) = self.ages.split_at_mut(middle);
+        let (positions, other_positions) = self.positions.split_at_mut(middle);
+        (
+            Moving { ids, reluctances, vaccines, isolated_until, states, infected_dates, ages, positions },
+            Moving {
+                ids: other_ids,
+                reluctances: other_reluctances,
+                vaccines: other_vaccines,
+                isolated_until: other_isolated_until,
+                states: other_states,
+                infected_dates: other_infected_dates,
+                ages: other_ages,
+                positions: other_positions,
+            },
+        )
     }
-    // We need the world to know its size because the world is circular
-    pub fn sqr_distance(&self, other: &Person, world_width: f32, world_height: f32) -> f32 {
-        let diff_x = Person::min_diff(self.position.x, other.position.x, world_width);
-        let diff_y = Person::min_diff(self.position.y, other.position.y, world_height);
-        diff_x * diff_x + diff_y * diff_y
+    pub fn id(&self, index: usize) -> usize {
+        self.ids[index]
+    }
+    pub fn age(&self, index: usize) -> usize {
+        self.ages[index]
+    }
+    pub fn reluctance(&self, index: usize) -> f32 {
+        self.reluctances[index]
+    }
+    pub fn state(&self, index: usize) -> PersonState {
+        self.states[index]
+    }
+    pub fn position(&self, index: usize) -> &Location {
+        &self.positions[index]
+    }
+    pub fn set_position(&mut self, index: usize, position: Location) {
+        self.positions[index] = position;
+    }
+    /// Moves the person at `index` like `Person::move_random_with`
+    pub fn move_random_with<R: Rng>(&mut self, index: usize, rng: &mut R, max_speed: f32, world: &Dimensions, walls: &[Wall]) {
+        let isolated = self.ages[index] < self.isolated_until[index];
+        walk(rng, self.states[index], isolated, &mut self.positions[index], max_speed, world, walls);
+    }
+    /// Ages the person at `index` like `Person::update_age_with`
+    pub fn update_age_with<R: Rng>(&mut self, index: usize, rng: &mut R, strains: &Strains) -> Aged {
+        grow_older(
+            rng,
+            strains,
+            &mut self.states[index],
+            &mut self.infected_dates[index],
+            &mut self.ages[index],
+            self.isolated_until[index],
+            protection(self.vaccines[index]),
+        )
     }
 }
 
@@ -123,14 +521,132 @@ mod tests {
             id: 1,
             state: PersonState::Recovered(true),
             age: 0,
+            years: 0,
             infected_date: 0,
+            reluctance: 0.0,
+            vaccine: None,
+            isolated_until: 0,
+            detected: false,
+            infected: false,
+            averted: false,
             home: position.clone(),
             position
         };
         for _ in 0..10 {
-            person.move_random(10.0, 100.0, 100.0);
+            person.move_random(10.0, &Dimensions::new(100.0, 100.0), &[]);
             assert_eq!(person.position.x, 10.0);
             assert_eq!(person.position.y, 10.0);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn people_dont_walk_through_walls() {
+        let walls = [Wall::new(40.0, 0.0, 40.0, 100.0), Wall::new(50.0, 0.0, 50.0, 100.0)];
+        let mut person = Person::new(45.0, 50.0, 1);
+        for _ in 0..100 {
+            person.move_random(10.0, &Dimensions::new(100.0, 100.0), &walls);
+            assert!(person.position.x > 40.0 && person.position.x < 50.0);
+        }
+    }
+
+    #[test]
+    fn people_come_back_at_the_other_side() {
+        let world = Dimensions::new(100.0, 50.0);
+        let mut person = Person::new(99.5, 0.5, 1);
+        let mut crossed = false;
+        for _ in 0..1000 {
+            let before = person.position.clone();
+            person.move_random(1.0, &world, &[]);
+            let position = &person.position;
+            assert!(position.x >= 0.0 && position.x < 100.0 && position.y >= 0.0 && position.y < 50.0);
+            // a step is never longer than the speed allows, also not across the border
+            let (diff_x, diff_y) = world.delta(&before, position);
+            assert!(diff_x.abs() <= 1.001 && diff_y.abs() <= 1.001);
+            crossed |= (position.x - before.x).abs() > 1.0 || (position.y - before.y).abs() > 1.0;
+        }
+        assert!(crossed);
+    }
+
+    #[test]
+    fn exposed_people_become_infectious_after_incubation() {
+        let mut virus = Virus::corona();
+        virus.incubation_time = 2;
+        virus.infection_rate = 1.0;
+        let mut strains = Strains::new();
+        let strain = strains.add(virus.clone());
+        let mut person = Person::new(10.0, 10.0, 1);
+        assert!(!person.was_infected());
+        person.infect(strain, &virus);
+        assert_eq!(person.get_state(), PersonState::Exposed(strain));
+        assert!(person.was_infected());
+        for _ in 0..2 {
+            person.update_age(&strains);
+            assert_eq!(person.get_state(), PersonState::Exposed(strain));
+        }
+        person.update_age(&strains);
+        assert_eq!(person.get_state(), PersonState::Infectious(strain));
+    }
+
+    #[test]
+    fn the_state_only_holds_the_strain() {
+        assert!(std::mem::size_of::<PersonState>() <= 8);
+    }
+
+    #[test]
+    fn vaccine_protects_against_infection_and_death() {
+        let mut virus = Virus::corona();
+        virus.infection_rate = 1.0;
+        virus.mortality_rate = 1.0;
+        virus.recovery_time = 0;
+        let mut strains = Strains::new();
+        let strain = strains.add(virus.clone());
+        let mut person = Person::new(10.0, 10.0, 1);
+        person.vaccinate(Protection { infection: 1.0, death: 0.0 });
+        assert!(person.is_vaccinated());
+        person.infect(strain, &virus);
+        assert_eq!(person.get_state(), PersonState::Susceptible);
+        person.vaccinate(Protection { infection: 0.0, death: 1.0 });
+        person.infect(strain, &virus);
+        person.update_age(&strains);
+        assert_eq!(person.get_state(), PersonState::Recovered(false));
+    }
+
+    #[test]
+    fn people_keep_every_field_of_a_person() {
+        let mut people = People::default();
+        for id in 0..5 {
+            let mut person = Person::new(id as f32, 10.0, id);
+            person.isolate(id);
+            person.set_state(PersonState::Recovered(id % 2 == 0));
+            people.push(person);
+        }
+        let mut changed = people.get(1);
+        changed.vaccinate(Protection { infection: 0.5, death: 0.5 });
+        changed.detect();
+        people.set(1, &changed);
+        let removed = people.swap_remove(0);
+        assert_eq!(removed.get_id(), 0);
+        assert_eq!(people.len(), 4);
+        // the last person took the place of the removed one
+        assert_eq!(people.id(0), 4);
+        let person = people.get(1);
+        assert_eq!(format!("{:?}", person), format!("{:?}", changed));
+        let parts = people.moving().split(3);
+        assert_eq!(parts.iter().map(|part| part.len()).collect::<Vec<usize>>(), vec![3, 1]);
+        assert_eq!(parts[1].id(0), 3);
+    }
+
+    #[test]
+    fn isolated_people_stay_home_for_the_isolation_period() {
+        let mut person = Person::new(10.0, 10.0, 1);
+        person.isolate(3);
+        for _ in 0..3 {
+            assert!(person.is_isolated());
+            person.move_random(10.0, &Dimensions::new(100.0, 100.0), &[]);
+            person.update_age(&Strains::new());
+            assert_eq!(person.position.x, 10.0);
+            assert_eq!(person.position.y, 10.0);
+        }
+        assert!(!person.is_isolated());
+    }
+}
@@ -0,0 +1,286 @@
+use super::person::Person;
+use super::world::Stats;
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// Non-pharmaceutical interventions
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Intervention {
+    /// Reduces the movement speed
+    Lockdown,
+    /// Reduces the distance over which the virus spreads
+    SocialDistancing,
+    /// Reduces the infection rate
+    MaskMandate,
+}
+
+/// The combined effect of all active policies, as factors on the normal values
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Modifiers {
+    pub move_speed: f32,
+    pub distance: f32,
+    pub infection_rate: f32,
+}
+
+impl Modifiers {
+    pub fn none() -> Modifiers {
+        Modifiers {
+            move_speed: 1.0,
+            distance: 1.0,
+            infection_rate: 1.0,
+        }
+    }
+    /// Policies that everybody follows, with the same effect as these modifiers
+    pub fn to_policies(&self) -> Vec<Policy> {
+        let factors = [
+            (Intervention::Lockdown, self.move_speed),
+            (Intervention::SocialDistancing, self.distance),
+            (Intervention::MaskMandate, self.infection_rate),
+        ];
+        factors
+            .iter()
+            .filter(|(_, factor)| *factor != 1.0)
+            .map(|(intervention, factor)| Policy::scheduled(*intervention, *factor, 0, 0))
+            .collect()
+    }
+    /// The largest distance factor that anybody can get from `policies`
+    pub fn max_distance(policies: &[Policy]) -> f32 {
+        policies
+            .iter()
+            .filter(|policy| policy.intervention == Intervention::SocialDistancing)
+            .map(|policy| policy.factor.max(1.0))
+            .product()
+    }
+    /// The combined effect of the policies that `person` follows
+    pub fn for_person(policies: &[Policy], person: &Person) -> Modifiers {
+        Modifiers::for_reluctance(policies, person.get_reluctance())
+    }
+    /// The combined effect of the policies that people with reluctance `reluctance` follow
+    pub fn for_reluctance(policies: &[Policy], reluctance: f32) -> Modifiers {
+        let mut modifiers = Modifiers::none();
+        for policy in policies.iter().filter(|policy| policy.is_followed_with(reluctance)) {
+            policy.apply(&mut modifiers);
+        }
+        modifiers
+    }
+}
+
+/// Decides on the modifiers for the next tick, based on the current stats
+pub type Controller = Box<dyn FnMut(&Stats) -> Modifiers>;
+
+/// An intervention that is active during a period of time.
+/// The period starts at a fixed tick, or when the fraction of infected people
+/// exceeds the trigger, and lasts for `duration` ticks (0 means forever).
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub intervention: Intervention,
+    /// Factor applied to the value the intervention works on, 0.2 keeps 20%
+    pub factor: f32,
+    pub start: usize,
+    pub duration: usize,
+    /// Fraction of infected people that starts the policy, 0 uses the start tick
+    pub trigger: f32,
+    /// Fraction of the people that follows the policy, the others ignore it
+    pub compliance: f32,
+    started: Option<usize>,
+}
+
+#[wasm_bindgen]
+impl Policy {
+    /// A policy from tick `start` up to (not including) tick `end`, an `end` of 0 never ends
+    pub fn scheduled(intervention: Intervention, factor: f32, start: usize, end: usize) -> Policy {
+        Policy {
+            intervention,
+            factor,
+            start,
+            duration: if end == 0 { 0 } else { end.saturating_sub(start).max(1) },
+            trigger: 0.0,
+            compliance: 1.0,
+            started: None,
+        }
+    }
+    /// A policy that starts once more than `infected` of the population is infected
+    pub fn triggered(intervention: Intervention, factor: f32, infected: f32, duration: usize) -> Policy {
+        Policy {
+            intervention,
+            factor,
+            start: 0,
+            duration,
+            trigger: infected,
+            compliance: 1.0,
+            started: None,
+        }
+    }
+    pub fn is_active(&self, tick: usize) -> bool {
+        match self.started {
+            Some(start) => tick >= start && (self.duration == 0 || tick < start + self.duration),
+            None => false,
+        }
+    }
+}
+
+impl Policy {
+    /// Starts the policy when its time has come, returns whether it is active
+    pub fn update(&mut self, tick: usize, stats: &Stats) -> bool {
+        if self.started.is_none() {
+            let triggered = if self.trigger > 0.0 {
+                stats.infected > self.trigger
+            } else {
+                tick >= self.start
+            };
+            if triggered {
+                self.started = Some(tick);
+            }
+        }
+        self.is_active(tick)
+    }
+    /// People whose reluctance is lower than the compliance follow the policy
+    pub fn is_followed_by(&self, person: &Person) -> bool {
+        self.is_followed_with(person.get_reluctance())
+    }
+    pub fn is_followed_with(&self, reluctance: f32) -> bool {
+        reluctance < self.compliance
+    }
+    /// Applies the effect of this policy on top of `modifiers`
+    pub fn apply(&self, modifiers: &mut Modifiers) {
+        match self.intervention {
+            Intervention::Lockdown => modifiers.move_speed *= self.factor,
+            Intervention::SocialDistancing => modifiers.distance *= self.factor,
+            Intervention::MaskMandate => modifiers.infection_rate *= self.factor,
+        }
+    }
+}
+
+/// Parses policies as `<intervention>,<factor>[,start=<tick>][,end=<tick>][,trigger=<fraction>][,duration=<ticks>][,compliance=<fraction>]`,
+/// e.g. `lockdown,0.2,start=50,end=150` or `masks,0.5,trigger=0.05,duration=100,compliance=0.7`
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.split(',').map(|part| part.trim());
+        let intervention = match parts.next() {
+            Some("lockdown") => Intervention::Lockdown,
+            Some("distancing") => Intervention::SocialDistancing,
+            Some("masks") => Intervention::MaskMandate,
+            other => return Err(format!("Unknown intervention '{}'", other.unwrap_or(""))),
+        };
+        let factor = parts
+            .next()
+            .and_then(|factor| factor.parse::<f32>().ok())
+            .ok_or_else(|| format!("Missing factor in policy '{}'", text))?;
+        let mut policy = Policy::scheduled(intervention, factor, 0, 0);
+        let mut end = None;
+        for part in parts {
+            let mut key_value = part.splitn(2, '=');
+            let key = key_value.next().unwrap_or("");
+            let value = key_value.next().unwrap_or("");
+            let invalid = || format!("Invalid value '{}' for '{}' in policy '{}'", value, key, text);
+            match key {
+                "start" => policy.start = value.parse().map_err(|_| invalid())?,
+                "end" => end = Some(value.parse::<usize>().map_err(|_| invalid())?),
+                "trigger" => policy.trigger = value.parse().map_err(|_| invalid())?,
+                "duration" => policy.duration = value.parse().map_err(|_| invalid())?,
+                "compliance" => policy.compliance = value.parse().map_err(|_| invalid())?,
+                _ => return Err(format!("Unknown setting '{}' in policy '{}'", key, text)),
+            }
+        }
+        if let Some(end) = end {
+            policy.duration = end.saturating_sub(policy.start).max(1);
+        }
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(infected: f32) -> Stats {
+        Stats {
+            susceptable: 1.0 - infected,
+            exposed: 0.0,
+            infected,
+            recovered: 0.0,
+            vaccinated: 0.0,
+            cases: 0.0,
+            detected: 0.0,
+            isolated: 0.0,
+            averted: 0.0,
+            incidence: 0.0,
+        }
+    }
+
+    #[test]
+    fn scheduled_policy_is_active_between_start_and_end() {
+        let mut policy = Policy::scheduled(Intervention::Lockdown, 0.5, 2, 4);
+        let active: Vec<bool> = (0..6).map(|tick| policy.update(tick, &stats(0.0))).collect();
+        assert_eq!(active, vec![false, false, true, true, false, false]);
+    }
+
+    #[test]
+    fn triggered_policy_starts_when_infections_rise() {
+        let mut policy = Policy::triggered(Intervention::MaskMandate, 0.5, 0.05, 2);
+        assert!(!policy.update(0, &stats(0.01)));
+        assert!(policy.update(1, &stats(0.06)));
+        assert!(policy.update(2, &stats(0.01)));
+        assert!(!policy.update(3, &stats(0.06)));
+    }
+
+    #[test]
+    fn policies_combine() {
+        let mut modifiers = Modifiers::none();
+        Policy::scheduled(Intervention::Lockdown, 0.5, 0, 0).apply(&mut modifiers);
+        Policy::scheduled(Intervention::Lockdown, 0.5, 0, 0).apply(&mut modifiers);
+        Policy::scheduled(Intervention::MaskMandate, 0.3, 0, 0).apply(&mut modifiers);
+        assert_eq!(modifiers.move_speed, 0.25);
+        assert_eq!(modifiers.distance, 1.0);
+        assert_eq!(modifiers.infection_rate, 0.3);
+    }
+
+    #[test]
+    fn modifiers_become_policies() {
+        let mut modifiers = Modifiers::none();
+        assert!(modifiers.to_policies().is_empty());
+        modifiers.move_speed = 0.5;
+        modifiers.infection_rate = 0.2;
+        let mut combined = Modifiers::none();
+        for policy in modifiers.to_policies() {
+            policy.apply(&mut combined);
+        }
+        assert_eq!(combined, modifiers);
+    }
+
+    #[test]
+    fn only_compliant_people_follow_policies() {
+        let mut policy = Policy::scheduled(Intervention::Lockdown, 0.0, 0, 0);
+        policy.compliance = 0.25;
+        let people: Vec<Person> = (0..1000).map(|id| Person::new(0.0, 0.0, id)).collect();
+        let following = people.iter().filter(|person| policy.is_followed_by(person)).count();
+        assert!(following > 180 && following < 320);
+        for person in people.iter() {
+            let modifiers = Modifiers::for_person(&[policy.clone()], person);
+            let expected = if policy.is_followed_by(person) { 0.0 } else { 1.0 };
+            assert_eq!(modifiers.move_speed, expected);
+        }
+    }
+
+    #[test]
+    fn parse_policies() {
+        let policy: Policy = "lockdown,0.2,start=50,end=150".parse().unwrap();
+        assert_eq!(policy.intervention, Intervention::Lockdown);
+        assert_eq!(policy.factor, 0.2);
+        assert_eq!(policy.start, 50);
+        assert_eq!(policy.duration, 100);
+        assert_eq!(policy.compliance, 1.0);
+        let policy: Policy = "masks, 0.5, trigger=0.05, duration=10, compliance=0.7".parse().unwrap();
+        assert_eq!(policy.intervention, Intervention::MaskMandate);
+        assert_eq!(policy.trigger, 0.05);
+        assert_eq!(policy.duration, 10);
+        assert_eq!(policy.compliance, 0.7);
+        assert!("curfew,0.5".parse::<Policy>().is_err());
+        assert!("distancing".parse::<Policy>().is_err());
+        assert!("distancing,0.5,start=soon".parse::<Policy>().is_err());
+    }
+}
@@ -1,262 +1,639 @@
-use super::person::{Person, PersonState};
-use super::virus::Virus;
-use std::iter::Flatten;
-use std::thread;
-use std::sync::{Arc, Mutex};
-use std::slice::{Iter, IterMut};
+use super::counts::Counts;
+use super::density::DensityMap;
+use super::geometry::{Dimensions, Grid};
+use super::neighbours::{GridIndex, NeighbourIndex, SpatialIndex};
+use super::person::{Aged, Location, Moving, People, Person, PersonState};
+use super::policy::{Modifiers, Policy};
+use super::virus::{Strain, Strains};
+use super::wall::{is_blocked, Wall};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
 
 extern crate web_sys;
 
 // A macro to provide `println!(..)`-style syntax for `console.log` logging.
+// The console only exists in the browser, natively the message is dropped.
 macro_rules! log {
     ( $( $t:tt )* ) => {
+        #[cfg(target_arch = "wasm32")]
         web_sys::console::log_1(&format!( $( $t )* ).into());
     }
 }
 
+const PEOPLE_PER_THREAD: usize = 1000;
+// The neighbour index is built again once more people than this, or than 1 in 8 people,
+// came or left since it was built
+const UNINDEXED_PEOPLE: usize = 64;
+// Marks the people that left, or that came, since the neighbour index was built
+const REMOVED: usize = usize::MAX;
+
+// A strain reaching a person, policies may have lowered its infection rate
+#[derive(Copy, Clone, Debug)]
+struct Infection {
+  strain: Strain,
+  infection_rate: f32,
+  // a quarantine averted the infection
+  averted: bool,
+}
+
+// The infections of the people by id
+type Infections = HashMap<usize, Infection>;
+
+// The people as they were when the neighbour index was built, people come and go in between
+#[derive(Clone, Debug, Default)]
+struct Snapshot {
+  // the index in `people` of everybody in the neighbour index, REMOVED for the people that left
+  people: Vec<usize>,
+  // where each person in `people` is in the snapshot, REMOVED for the people that came
+  ranks: Vec<usize>,
+  // the people that came since the index was built, they are checked one by one
+  unindexed: Vec<usize>,
+  // the number of people that left since the index was built
+  removed: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct Population {
-  people: Vec<Vec<Vec<Person>>>,
-  grid_width: f32,
-  grid_height: f32
+  // everybody in no particular order, the last person takes the place of a person that leaves
+  people: People,
+  world: Dimensions,
+  walls: Vec<Wall>,
+  // people can't enter the places where this map has no density
+  impassable: Option<DensityMap>,
+  // ids are never reused, so they stay valid while people come and go
+  next_id: usize,
+  // where each person is in `people`, by id
+  slots: HashMap<usize, usize>,
+  // finds the people close to somebody
+  index: Box<dyn NeighbourIndex>,
+  snapshot: Snapshot,
+  // the distance the index is tuned for
+  distance: Option<f32>,
+  // makes the movement the same on every run
+  seed: Option<u64>,
+  // the number of threads to use, all threads of the default pool when not set
+  threads: Option<usize>,
+  // the worker threads, reused every tick, rayon's global pool when not set
+  pool: Option<Arc<ThreadPool>>,
+  // the people in each state, changed along with the people
+  counts: Counts,
+  // the people infected by the last call to infect_closeby
+  incidence: usize,
+}
+
+/// A person that can be changed, the changes go back into the population, and the counts
+/// of the population follow them, when it is dropped
+pub struct PersonMut<'a> {
+  person: Person,
+  index: usize,
+  people: &'a mut People,
+  before: Counts,
+  counts: &'a mut Counts,
+}
+
+impl<'a> PersonMut<'a> {
+  fn new(index: usize, people: &'a mut People, counts: &'a mut Counts) -> PersonMut<'a> {
+    let person = people.get(index);
+    PersonMut { before: Counts::of(&person), person, index, people, counts }
+  }
+}
+
+impl Deref for PersonMut<'_> {
+  type Target = Person;
+  fn deref(&self) -> &Person {
+    &self.person
+  }
+}
+
+impl DerefMut for PersonMut<'_> {
+  fn deref_mut(&mut self) -> &mut Person {
+    &mut self.person
+  }
+}
+
+impl Drop for PersonMut<'_> {
+  fn drop(&mut self) {
+    self.people.set(self.index, &self.person);
+    self.counts.change(&self.before, &Counts::of(&self.person));
+  }
 }
 
 impl Population {
   pub fn new(world_width: f32, world_height: f32, num_grid_width: usize, num_grid_height: usize) -> Population {
-    let mut people: Vec<Vec<Vec<Person>>> = Vec::with_capacity(num_grid_width);
-    for i in 0..num_grid_width {
-      people.push(Vec::with_capacity(num_grid_height));
-      for _ in 0..num_grid_height {
-        people[i].push(Vec::new());
+    let world = Dimensions::new(world_width, world_height);
+    Population {
+      people: People::default(),
+      world,
+      walls: Vec::new(),
+      impassable: None,
+      next_id: 0,
+      slots: HashMap::new(),
+      index: Box::new(GridIndex::new(Grid::new(world, num_grid_width, num_grid_height))),
+      snapshot: Snapshot::default(),
+      distance: None,
+      seed: None,
+      threads: None,
+      pool: None,
+      counts: Counts::default(),
+      incidence: 0,
+    }
+  }
+  /// Uses `threads` threads for large populations, 0 uses all the threads of the default pool.
+  /// Natively the population gets its own pool with that many threads, in the browser the pool
+  /// started from JavaScript limits the number of threads.
+  pub fn set_threads(&mut self, threads: usize) -> Result<(), String> {
+    self.threads = if threads == 0 { None } else { Some(threads) };
+    self.pool = None;
+    if threads > 1 && cfg!(not(target_arch = "wasm32")) {
+      let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|error| format!("Could not start {} threads: {}", threads, error))?;
+      self.pool = Some(Arc::new(pool));
+    }
+    Ok(())
+  }
+  /// The number of threads that large populations use, never more than the pool that runs them has.
+  /// In the browser this needs the `wasm-threads` feature and a thread pool started from JavaScript.
+  pub fn threads(&self) -> usize {
+    if cfg!(all(target_arch = "wasm32", not(feature = "wasm-threads"))) {
+      return 1;
+    }
+    let pool_size = match &self.pool {
+      Some(pool) => pool.current_num_threads(),
+      None => rayon::current_num_threads(),
+    };
+    self.threads.map_or(pool_size, |threads| threads.min(pool_size))
+  }
+  // Starting threads only pays off when every thread gets enough people
+  fn threads_to_use(&self) -> usize {
+    (self.len() / PEOPLE_PER_THREAD).clamp(1, self.threads())
+  }
+  fn run_in_pool<R, F>(pool: &Option<Arc<ThreadPool>>, operation: F) -> R
+  where
+    R: Send,
+    F: FnOnce() -> R + Send,
+  {
+    match pool {
+      Some(pool) => pool.install(operation),
+      None => operation(),
+    }
+  }
+  /// Builds the neighbour index again. Moving people does this every tick,
+  /// adding and removing people does it once enough people came or left.
+  pub fn reindex(&mut self) {
+    self.index.build(self.people.positions());
+    let snapshot = &mut self.snapshot;
+    snapshot.people = (0..self.people.len()).collect();
+    snapshot.ranks = (0..self.people.len()).collect();
+    snapshot.unindexed.clear();
+    snapshot.removed = 0;
+  }
+  /// Tunes the neighbour index to find the people within `distance` of somebody.
+  /// Searches work with any distance, this only changes how fast they are.
+  pub fn fit_index(&mut self, distance: f32) {
+    self.distance = Some(distance);
+    if self.index.fit(distance) {
+      self.reindex();
+    }
+  }
+  /// Finds the people close to somebody with `index` from now on
+  pub fn set_index(&mut self, index: SpatialIndex) {
+    self.index = index.create(self.world);
+    if let Some(distance) = self.distance {
+      self.index.fit(distance);
+    }
+    self.reindex();
+  }
+  pub fn spatial_index(&self) -> SpatialIndex {
+    self.index.kind()
+  }
+  fn reindex_if_stale(&mut self) {
+    let changes = self.snapshot.unindexed.len() + self.snapshot.removed;
+    if changes > UNINDEXED_PEOPLE.max(self.people.len() / 8) {
+      self.reindex();
+    }
+  }
+  /// Adds a person and returns its id, fails when another person has that id already
+  pub fn add(&mut self, person: Person) -> Result<usize, String> {
+    let id = person.get_id();
+    if self.slots.contains_key(&id) {
+      return Err(format!("There is already a person with id {}", id));
+    }
+    self.insert(person);
+    Ok(id)
+  }
+  fn insert(&mut self, person: Person) {
+    self.next_id = self.next_id.max(person.get_id() + 1);
+    self.slots.insert(person.get_id(), self.people.len());
+    self.snapshot.ranks.push(REMOVED);
+    self.snapshot.unindexed.push(self.people.len());
+    self.counts += Counts::of(&person);
+    self.people.push(person);
+    self.reindex_if_stale();
+  }
+  /// Adds a person coming from elsewhere, the person gets the next free id in this population,
+  /// which is returned.
+  pub fn add_with_next_id(&mut self, mut person: Person) -> usize {
+    let id = self.next_id;
+    person.set_id(id);
+    self.insert(person);
+    id
+  }
+  /// Adds a new susceptible person at (x, y) with the next free id
+  pub fn add_new(&mut self, x: f32, y: f32) -> PersonMut<'_> {
+    let id = self.add_with_next_id(Person::new(x, y, 0));
+    self.get_mut(id).unwrap()
+  }
+  /// Removes a random person that is still alive from the population.
+  pub fn take_random(&mut self) -> Option<Person> {
+    let alive: Vec<usize> = self.iter()
+      .filter(|person| person.get_state() != PersonState::Recovered(true))
+      .map(|person| person.get_id())
+      .collect();
+    if alive.is_empty() {
+      return None;
+    }
+    self.remove(alive[rand::thread_rng().gen_range(0, alive.len())])
+  }
+  /// Removes the person with id `id` from the population, the ids of the others don't change
+  pub fn remove(&mut self, id: usize) -> Option<Person> {
+    let removed = self.slots.remove(&id)?;
+    let last = self.people.len() - 1;
+    match self.snapshot.ranks[removed] {
+      REMOVED => self.snapshot.unindexed.retain(|person| *person != removed),
+      rank => {
+        self.snapshot.people[rank] = REMOVED;
+        self.snapshot.removed += 1;
       }
     }
-    Population {
-      people,
-      grid_width: world_width / num_grid_width as f32,
-      grid_height: world_height / num_grid_height as f32,
-    }
-  }
-  #[cfg(target_arch = "wasm32")]
-  fn num_threads(&self) -> u8 {
-    1
-  }
-  #[cfg(not(target_arch = "wasm32"))]
-  fn num_threads(&self) -> u8 {
-    8
-  }
-  fn get_indexes(&self, x: f32, y: f32) -> (usize, usize) {
-    let mut grid_x = (x / self.grid_width).floor() as usize;
-    let mut grid_y = (y / self.grid_height).floor() as usize;
-    grid_x = grid_x % self.people.len();
-    grid_y = grid_y % self.people[0].len();
-    (grid_x, grid_y)
-  }
-  pub fn add(&mut self, person: Person) {
-    let (i, j) = self.get_indexes(person.position.x, person.position.y);
-    self.people[i][j].push(person);
-  }
-  pub fn iter(&self) -> std::iter::Flatten<Flatten<Iter<'_, Vec<Vec<Person>>>>> {
-    self.people.iter().flatten().flatten()
-  }
-  fn iter_mut(&mut self) -> std::iter::Flatten<Flatten<IterMut<'_, Vec<Vec<Person>>>>> {
-    self.people.iter_mut().flatten().flatten()
-  }
-  pub fn update_positions(&mut self, move_speed: f32) {
-    let world_width = self.grid_width * self.people.len() as f32;
-    let world_height = self.grid_width * self.people[0].len() as f32;
-    let current_age = self.iter().next().unwrap().age;
-    for row in 0..self.people.len() {
-      for col in 0..self.people[row].len() {
-        let mut index = 0;
-        while index < self.people[row][col].len() {
-          let mut removed_item = false;
-          if self.people[row][col][index].age == current_age {
-            self.people[row][col][index].move_random(move_speed, world_width, world_height);
-            self.people[row][col][index].update_age();
-            let new_position = &self.people[row][col][index].position;
-            let (new_x, new_y) = self.get_indexes(new_position.x, new_position.y);
-
-            if new_x != row || new_y != col {
-              let person = self.people[row][col].remove(index);
-              removed_item = true;
-              self.people[new_x][new_y].push(person);
-            }
-          }
-          if !removed_item {
-            index += 1;
+    let person = self.people.swap_remove(removed);
+    self.snapshot.ranks.swap_remove(removed);
+    self.counts -= Counts::of(&person);
+    if removed != last {
+      self.slots.insert(self.people.id(removed), removed);
+      match self.snapshot.ranks[removed] {
+        REMOVED => {
+          for person in self.snapshot.unindexed.iter_mut().filter(|person| **person == last) {
+            *person = removed;
           }
         }
+        rank => self.snapshot.people[rank] = removed,
       }
     }
+    self.reindex_if_stale();
+    Some(person)
   }
-  fn people_from(&self, box_x: isize, box_y: isize) -> Iter<Person> {
-    let mut box_x = box_x;
-    let mut box_y = box_y;
-    while box_x < 0 {
-      box_x += self.people.len() as isize;
-    }
-    if box_x > (self.people.len() - 1) as isize {
-      box_x -= self.people.len() as isize;
-    }
-    while box_y < 0 {
-      box_y += self.people[0].len() as isize;
-    }
-    if box_y > (self.people[0].len() - 1) as isize {
-      box_y -= self.people[0].len() as isize;
-    }
-    self.people[box_x as usize][box_y as usize].iter()
-  }
-  fn infections_for_people_within_box(&self, box_x: usize, box_y: usize) -> Vec<(usize, Virus)> {
-    let mut infections: Vec<(usize, Virus)> = Vec::new();
-    let world_width = self.grid_width * self.people.len() as f32;
-    let world_height = self.grid_width * self.people[0].len() as f32;
-    for person1 in self.people[box_x][box_y].iter() {
-      let box_x = box_x as isize;
-      let box_y = box_y as isize;
-      if let PersonState::Infectious(virus) = person1.get_state() {
-        for x in box_x-1..box_x+2 {
-          for y in box_y-1..box_y+2 {
-            for person2 in self.people_from(x, y) {
-              let dist = person1.sqr_distance(&person2, world_width, world_height);
-              if dist < virus.distance * virus.distance {
-                infections.push((person2.get_id(), virus.clone()));
-              }
-            }
+  /// A copy of the person with id `id`
+  pub fn get(&self, id: usize) -> Option<Person> {
+    self.slots.get(&id).map(|index| self.people.get(*index))
+  }
+  /// The person with id `id`, don't change the id or the position through this reference
+  pub fn get_mut(&mut self, id: usize) -> Option<PersonMut<'_>> {
+    let index = *self.slots.get(&id)?;
+    Some(PersonMut::new(index, &mut self.people, &mut self.counts))
+  }
+  pub fn len(&self) -> usize {
+    self.people.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+  pub fn add_wall(&mut self, wall: Wall) {
+    self.walls.push(wall);
+  }
+  pub fn walls(&self) -> &[Wall] {
+    &self.walls
+  }
+  pub fn set_seed(&mut self, seed: u64) {
+    self.seed = Some(seed);
+  }
+  pub fn set_impassable(&mut self, density: DensityMap) {
+    self.impassable = Some(density);
+  }
+  /// Copies of everybody
+  pub fn iter(&self) -> impl Iterator<Item = Person> + '_ {
+    (0..self.people.len()).map(move |index| self.people.get(index))
+  }
+  /// Applies `change` to everybody, don't change the ids or the positions of people this way
+  pub fn for_each_mut<F>(&mut self, mut change: F)
+  where
+    F: FnMut(&mut Person),
+  {
+    for index in 0..self.people.len() {
+      change(&mut PersonMut::new(index, &mut self.people, &mut self.counts));
+    }
+  }
+  /// The number of people in each state, without looking at everybody
+  pub fn counts(&self) -> Counts {
+    self.counts
+  }
+  /// The number of people that got infected by the last call to `infect_closeby`
+  pub fn incidence(&self) -> usize {
+    self.incidence
+  }
+  /// Moves and ages everybody, `policies` are the active policies that may slow people down,
+  /// `strains` holds the viruses people carry.
+  /// Everybody moves at the same time, the neighbour index is built again afterwards.
+  pub fn update_positions(&mut self, move_speed: f32, policies: &[Policy], strains: &Strains) {
+    let world = self.world;
+    let threads = self.threads_to_use();
+    let people_per_thread = self.people.len().div_ceil(threads).max(1);
+    let walls = &self.walls;
+    let impassable = &self.impassable;
+    let seed = self.seed;
+    // returns the counts the people left and entered while they aged
+    let move_people = |mut people: Moving| {
+      let (mut left, mut entered) = (Counts::default(), Counts::default());
+      for person in 0..people.len() {
+        let speed = move_speed * Modifiers::for_reluctance(policies, people.reluctance(person)).move_speed;
+        let aged = match seed {
+          Some(seed) => {
+            let person_seed = Population::person_seed(seed, people.id(person), people.age(person));
+            let mut rng = SmallRng::seed_from_u64(person_seed);
+            Population::move_person(&mut people, person, &mut rng, speed, &world, walls, impassable, strains)
           }
+          None => {
+            Population::move_person(&mut people, person, &mut rand::thread_rng(), speed, &world, walls, impassable, strains)
+          }
+        };
+        if aged != Aged::default() {
+          let (person_left, person_entered) = Counts::aged(people.state(person), &aged);
+          left += person_left;
+          entered += person_entered;
         }
       }
-    }
-    infections
+      (left, entered)
+    };
+    let people = self.people.moving();
+    let (left, entered) = if threads > 1 {
+      Population::run_in_pool(&self.pool, || {
+        people.split(people_per_thread).into_par_iter().map(move_people).reduce(
+          || (Counts::default(), Counts::default()),
+          |(mut left, mut entered), (more_left, more_entered)| {
+            left += more_left;
+            entered += more_entered;
+            (left, entered)
+          },
+        )
+      })
+    } else {
+      move_people(people)
+    };
+    self.counts.change(&left, &entered);
+    self.reindex();
   }
-  fn infect_closeby_single_threaded(&mut self) -> Vec<Option<Virus>> {
-    let mut to_infect: Vec<Option<Virus>> = Vec::new();
-    for _ in self.iter() {
-      to_infect.push(None);
+  #[allow(clippy::too_many_arguments)]
+  fn move_person<R: Rng>(
+    people: &mut Moving,
+    person: usize,
+    rng: &mut R,
+    speed: f32,
+    world: &Dimensions,
+    walls: &[Wall],
+    impassable: &Option<DensityMap>,
+    strains: &Strains,
+  ) -> Aged {
+    let old_position = people.position(person).clone();
+    people.move_random_with(person, rng, speed, world, walls);
+    if let Some(density) = impassable {
+      if !density.is_step_passable(&old_position, people.position(person), world) {
+        people.set_position(person, old_position);
+      }
     }
-    for box_x in 0..self.people.len() {
-      for box_y in 0..self.people[0].len() {
-        for infection in self.infections_for_people_within_box(box_x, box_y) {
-          to_infect[infection.0] = Some(infection.1);
-        }
+    people.update_age_with(person, rng, strains)
+  }
+  // Every person gets different random numbers every tick, whatever thread moves the person
+  fn person_seed(seed: u64, id: usize, age: usize) -> u64 {
+    seed ^ (id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (age as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+  }
+  // Calls `found` with the index in `people` and the position of everybody within `radius` of `center`
+  fn for_each_within(&self, center: &Location, radius: f32, found: &mut dyn FnMut(usize, &Location)) {
+    let snapshot = &self.snapshot;
+    self.index.within(center, radius, &mut |rank, position| {
+      if snapshot.people[rank] != REMOVED {
+        found(snapshot.people[rank], position);
+      }
+    });
+    for index in snapshot.unindexed.iter() {
+      let position = self.people.position(*index);
+      if center.sqr_distance(position, &self.world) < radius * radius {
+        found(*index, position);
       }
     }
-    to_infect
   }
-  fn infect_closeby_multithreaded(&mut self) -> Vec<Option<Virus>> {
-    let mut to_infect: Vec<Option<Virus>> = Vec::new();
-    for _ in self.iter() {
-      to_infect.push(None);
+  // Distancing only works when both people keep their distance, a mask protects others.
+  // Quarantined people don't infect anybody, the infections they would have caused are marked as averted.
+  // `person1` is the index in `people` of the person that may infect others.
+  fn infections_by(
+    &self,
+    person1: usize,
+    strains: &Strains,
+    policies: &[Policy],
+    max_distance: f32,
+    infections: &mut Infections,
+  ) {
+    let people = &self.people;
+    let isolated = people.is_isolated(person1);
+    if isolated && people.is_detected(person1) {
+      return;
     }
-    let mut boxes_to_test: Vec<(usize, usize)> = Vec::new();
-    for box_x in 0..self.people.len() {
-      for box_y in 0..self.people[0].len() {
-        if self.people[box_x][box_y].len() > 0 {
-          boxes_to_test.push((box_x, box_y));
+    if let PersonState::Infectious(strain) = people.state(person1) {
+      let virus = strains.get(strain);
+      let modifiers = Modifiers::for_reluctance(policies, people.reluctance(person1));
+      let infection_rate = virus.infection_rate * modifiers.infection_rate;
+      // the people around may keep a larger distance than this person
+      let reach = virus.distance * modifiers.distance.max(max_distance);
+      let position1 = people.position(person1);
+      self.for_each_within(position1, reach, &mut |person2, position2| {
+        let dist = position1.sqr_distance(position2, &self.world);
+        let distance = if policies.is_empty() {
+          virus.distance
+        } else {
+          virus.distance * modifiers.distance.max(Modifiers::for_reluctance(policies, people.reluctance(person2)).distance)
+        };
+        if dist < distance * distance && self.can_reach(position1, position2) {
+          // quarantined people are isolated without being detected
+          let infection = Infection { strain, infection_rate, averted: isolated };
+          Population::add_infection(infections, people.id(person2), infection);
         }
+      });
+    }
+  }
+  /// Copies of all people within `radius` of the location (x, y)
+  pub fn people_within(&self, x: f32, y: f32, radius: f32) -> Vec<Person> {
+    let mut people = Vec::new();
+    self.for_each_within(&Location { x, y }, radius, &mut |index, _| people.push(self.people.get(index)));
+    people
+  }
+  /// The person closest to the location (x, y), looking in ever larger circles around it
+  pub fn nearest(&self, x: f32, y: f32) -> Option<Person> {
+    let world = &self.world;
+    let center = Location { x, y };
+    // start at about the distance between people
+    let mut radius = (world.width * world.height / self.len().max(1) as f32).sqrt();
+    loop {
+      let nearest = self.people_within(x, y, radius).into_iter().min_by(|a, b| {
+        let distance_a = center.sqr_distance(&a.position, world);
+        let distance_b = center.sqr_distance(&b.position, world);
+        distance_a.partial_cmp(&distance_b).unwrap()
+      });
+      if nearest.is_some() || radius > world.width + world.height {
+        return nearest;
       }
+      radius *= 2.0;
     }
-    let boxes_to_test: Arc<Mutex<Vec<(usize, usize)>>> = Arc::new(Mutex::new(boxes_to_test));
-    // FIXME -> need to figure out how to remove this clone function
-    let population = Arc::new(self.clone());
-    let to_infect: Arc<Mutex<Vec<Option<Virus>>>> = Arc::new(Mutex::new(to_infect));
-    let mut threads = vec![];
-    for _ in 0..8 {
-      let boxes_to_test = boxes_to_test.clone();
-      let pop = population.clone();
-      let to_infect = to_infect.clone();
-      threads.push(thread::spawn(move || {
-        loop {
-          let box_to_check = {
-            let mut boxes = boxes_to_test.lock().unwrap();
-            if boxes.len() == 0 {
-              break
-            }
-            boxes.pop().unwrap()
-          };
-          for infection in (*pop).infections_for_people_within_box(box_to_check.0, box_to_check.1) {
-            let mut inf = to_infect.lock().unwrap();
-            inf[infection.0] = Some(infection.1);
-          }
-        }
-      }));
+  }
+  // Checks that no wall stands between two people, taking the wrapping of the world into account
+  fn can_reach(&self, position1: &Location, position2: &Location) -> bool {
+    if self.walls.is_empty() {
+      return true;
     }
-    for thread in threads {
-      let _res = thread.join();
+    let (diff_x, diff_y) = self.world.delta(position1, position2);
+    let target = Location { x: position1.x + diff_x, y: position1.y + diff_y };
+    !is_blocked(&self.walls, position1, &target)
+  }
+  // A real infection wins over an averted one
+  fn add_infection(to_infect: &mut Infections, id: usize, infection: Infection) {
+    if !infection.averted || !to_infect.contains_key(&id) {
+      to_infect.insert(id, infection);
     }
-    let to_infect = to_infect.lock().unwrap();
-    to_infect.to_vec()
   }
-  pub fn infect_closeby(&mut self) {
-    log!("Num threads {}", self.num_threads());
-    let to_infect = match self.num_threads() {
-      nt if nt > 1 => self.infect_closeby_multithreaded(),
-      _ => self.infect_closeby_single_threaded()
+  // Everybody in the order of the neighbour index, so that the searches of people close
+  // to each other find their neighbours in the cache
+  fn spatial_order(&self) -> Vec<usize> {
+    let people = &self.snapshot.people;
+    self.index.order().iter()
+      .map(|rank| people[*rank])
+      .filter(|person| *person != REMOVED)
+      .chain(self.snapshot.unindexed.iter().cloned())
+      .collect()
+  }
+  fn infect_closeby_single_threaded(&self, policies: &[Policy], strains: &Strains) -> Infections {
+    let mut to_infect: Infections = HashMap::new();
+    let max_distance = Modifiers::max_distance(policies);
+    for person in self.spatial_order() {
+      self.infections_by(person, strains, policies, max_distance, &mut to_infect);
+    }
+    to_infect
+  }
+  // The people are shared by the threads without copying them, every thread collects its own
+  // infections and the results are merged at the end
+  fn infect_closeby_multithreaded(&self, policies: &[Policy], strains: &Strains) -> Infections {
+    let max_distance = Modifiers::max_distance(policies);
+    let people_per_thread = self.people.len().div_ceil(self.threads_to_use()).max(1);
+    let order = self.spatial_order();
+    Population::run_in_pool(&self.pool, || {
+      order
+        .par_iter()
+        .with_min_len(people_per_thread)
+        .fold(HashMap::new, |mut infections: Infections, person| {
+          self.infections_by(*person, strains, policies, max_distance, &mut infections);
+          infections
+        })
+        .reduce(HashMap::new, |mut to_infect, infections| {
+          for (id, infection) in infections {
+            Population::add_infection(&mut to_infect, id, infection);
+          }
+          to_infect
+        })
+    })
+  }
+  /// Infects the people close to an infectious person, the active `policies` can
+  /// reduce the distance and the infection rate of the virus, `strains` holds the viruses.
+  /// Returns the expected number of infections that quarantined people would have caused.
+  pub fn infect_closeby(&mut self, policies: &[Policy], strains: &Strains) -> f32 {
+    // every infectious person would check the people that came one by one
+    if !self.snapshot.unindexed.is_empty() {
+      self.reindex();
+    }
+    log!("Num threads {}", self.threads_to_use());
+    let to_infect = match self.threads_to_use() {
+      nt if nt > 1 => self.infect_closeby_multithreaded(policies, strains),
+      _ => self.infect_closeby_single_threaded(policies, strains)
     };
-    for person in self.iter_mut() {
-      if let Some(virus) = &to_infect[person.get_id()] {
-        person.infect(virus.clone())
+    let mut averted = 0.0;
+    let mut infected = 0;
+    for (id, infection) in to_infect {
+      let mut virus = strains.get(infection.strain).clone();
+      virus.infection_rate = infection.infection_rate;
+      if let Some(mut person) = self.get_mut(id) {
+        if infection.averted {
+          averted += person.avert(&virus);
+        } else if person.infect(infection.strain, &virus) {
+          infected += 1;
+        }
       }
     }
+    self.incidence = infected;
+    averted
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-
-  #[test]
-  fn correct_amount_of_boxes_is_made() {
-    let population = Population::new(100.0, 100.0, 10, 5);
-    assert_eq!(population.people.len(), 10);
-    for i in 0..10 {
-      assert_eq!(population.people[i].len(), 5);
-    }
-  }
+  use crate::sir::policy::Intervention;
+  use crate::sir::person::Protection;
+  use crate::sir::virus::Virus;
 
   #[test]
   fn updating_positions_everybody_in_different_location() {
     let mut population = Population::new(100.0, 100.0, 10, 5);
     for index in 0..100 {
-      population.add(Person::new(index as f32, index as f32, index));
+      population.add(Person::new(index as f32, index as f32, index)).unwrap();
     }
-    population.update_positions(10.0);
+    population.update_positions(10.0, &[], &Strains::new());
     for person in population.iter() {
       assert!(person.position.x != person.get_id() as f32 && person.position.y != person.get_id() as f32);
     }
   }
 
   #[test]
-  fn updating_positions_everybody_in_correct_boxes() {
-    let mut population = Population::new(100.0, 100.0, 10, 5);
-    for index in 0..100 {
-      population.add(Person::new(index as f32, index as f32, index));
-    }
-    population.update_positions(10.0);
-    for row in 0..population.people.len() {
-      for col in 0..population.people[row].len() {
-        for index in 0..population.people[row][col].len() {
-          let person = &population.people[row][col][index];
-          let (x, y) = population.get_indexes(person.position.x, person.position.y);
-          assert_eq!(x, row);
-          assert_eq!(y, col);
-        }
+  fn updating_positions_everybody_is_found_at_the_new_place() {
+    for index in [SpatialIndex::Grid, SpatialIndex::KdTree].iter() {
+      let mut population = Population::new(100.0, 100.0, 10, 5);
+      population.set_index(*index);
+      for index in 0..100 {
+        population.add(Person::new(index as f32, index as f32, index)).unwrap();
       }
+      population.update_positions(10.0, &[], &Strains::new());
+      for person in population.iter() {
+        let found = population.people_within(person.position.x, person.position.y, 0.001);
+        assert!(found.iter().any(|other| other.get_id() == person.get_id()));
+      }
+      assert_eq!(population.people_within(50.0, 50.0, 1000.0).len(), 100);
     }
   }
 
   #[test]
-  fn people_are_added_to_correct_box() {
+  fn seeded_movement_is_the_same_every_time() {
     let mut population = Population::new(100.0, 100.0, 10, 10);
-    let person = Person::new(12.0, 23.0, 1);
-    population.add(person);
-    assert_eq!(population.people[1][2].len(), 1);
+    // enough people to move them on several threads
+    for index in 0..3000 {
+      population.add(Person::new_random(100.0, 100.0, index)).unwrap();
+    }
+    population.set_seed(42);
+    let mut other = population.clone();
+    for _ in 0..10 {
+      population.update_positions(10.0, &[], &Strains::new());
+      other.update_positions(10.0, &[], &Strains::new());
+    }
+    for person in population.iter() {
+      assert_eq!(other.get(person.get_id()).unwrap().position, person.position);
+    }
   }
 
   #[test]
   fn iterator_through_all_persons() {
     let mut population = Population::new(100.0, 100.0, 10, 10);
-    population.add(Person::new(12.0, 23.0, 1));
-    population.add(Person::new(32.0, 13.0, 0));
+    population.add(Person::new(12.0, 23.0, 1)).unwrap();
+    population.add(Person::new(32.0, 13.0, 0)).unwrap();
     let mut found = [false, false];
     for person in population.iter() {
       found[person.get_id()] = true;
@@ -265,35 +642,21 @@ mod tests {
     assert!(found[1]);
   }
 
-  #[test]
-  fn get_iterator_of_correct_box_with_wrapping() {
-    // We make a grid of 2x2 and put a single person in each
-    let mut population = Population::new(10.0, 10.0, 2, 2);
-    population.add(Person::new(2.0, 2.0, 0));
-    population.add(Person::new(7.0, 2.0, 1));
-    population.add(Person::new(2.0, 7.0, 2));
-    population.add(Person::new(7.0, 7.0, 3));
-    assert_eq!(population.people_from(0, 0).next().unwrap().get_id(), 0);
-    assert_eq!(population.people_from(1, 0).next().unwrap().get_id(), 1);
-    assert_eq!(population.people_from(0, 1).next().unwrap().get_id(), 2);
-    assert_eq!(population.people_from(1, 1).next().unwrap().get_id(), 3);
-    assert_eq!(population.people_from(-1, -1).next().unwrap().get_id(), 3);
-    assert_eq!(population.people_from(3, 3).next().unwrap().get_id(), 3);
-  }
-
   #[test]
   fn infect_closeby_users() {
       let mut virus = Virus::corona();
       virus.distance = 5.0;
       virus.infection_rate = 1.0;
+      let mut strains = Strains::new();
+      let strain = strains.add(virus.clone());
       let mut population = Population::new(10.0, 10.0, 2, 2);
       let mut infected_person = Person::new(2.0, 2.0, 0);
-      infected_person.infect(virus);
-      population.add(infected_person);
-      population.add(Person::new(3.0, 2.0, 1));
-      population.add(Person::new(2.0, 3.0, 2));
-      population.add(Person::new(7.0, 7.0, 3));
-      population.infect_closeby();
+      infected_person.infect(strain, &virus);
+      population.add(infected_person).unwrap();
+      population.add(Person::new(3.0, 2.0, 1)).unwrap();
+      population.add(Person::new(2.0, 3.0, 2)).unwrap();
+      population.add(Person::new(7.0, 7.0, 3)).unwrap();
+      population.infect_closeby(&[], &strains);
       let mut count = 0;
       for person in population.iter() {
         if let PersonState::Infectious(_virus) = person.get_state() {
@@ -302,5 +665,374 @@ mod tests {
       }
       assert_eq!(count, 3);
   }
-}
 
+  #[test]
+  fn threads_find_the_same_infections() {
+    let mut virus = Virus::corona();
+    virus.distance = 5.0;
+    let mut strains = Strains::new();
+    let strain = strains.add(virus);
+    let mut population = Population::new(100.0, 100.0, 20, 20);
+    for index in 0..2000 {
+      let mut person = Person::new_random(100.0, 100.0, index);
+      if index % 10 == 0 {
+        person.set_state(PersonState::Infectious(strain));
+      }
+      if index % 30 == 0 {
+        person.isolate(10);
+      }
+      population.add(person).unwrap();
+    }
+    population.reindex();
+    let single = population.infect_closeby_single_threaded(&[], &strains);
+    let multi = population.infect_closeby_multithreaded(&[], &strains);
+    assert!(!single.is_empty());
+    assert_eq!(single.len(), multi.len());
+    for (id, infection) in single.iter() {
+      assert_eq!(multi[id].averted, infection.averted);
+    }
+  }
+
+  #[test]
+  fn configure_the_number_of_threads() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    for index in 0..5000 {
+      population.add(Person::new_random(100.0, 100.0, index)).unwrap();
+    }
+    population.set_threads(1).unwrap();
+    assert_eq!(population.threads_to_use(), 1);
+    population.set_threads(3).unwrap();
+    assert_eq!(population.threads(), 3);
+    assert_eq!(population.threads_to_use(), 3);
+    for _ in 0..3 {
+      population.update_positions(10.0, &[], &Strains::new());
+      population.infect_closeby(&[], &Strains::new());
+    }
+    assert_eq!(population.len(), 5000);
+    population.set_threads(0).unwrap();
+    assert_eq!(population.threads(), rayon::current_num_threads());
+  }
+
+  #[test]
+  fn no_contact_is_missed_whatever_the_index() {
+    let mut distancing = Policy::scheduled(Intervention::SocialDistancing, 2.5, 0, 0);
+    distancing.compliance = 0.5;
+    let policies = [distancing];
+    for distance in [0.5, 5.0, 12.0, 40.0, 150.0].iter() {
+      let mut virus = Virus::corona();
+      virus.distance = *distance;
+      let mut strains = Strains::new();
+      let strain = strains.add(virus);
+      let mut population = Population::new(100.0, 100.0, 1, 1);
+      for index in 0..300 {
+        let mut person = Person::new_random(100.0, 100.0, index);
+        if index % 10 == 0 {
+          person.set_state(PersonState::Infectious(strain));
+        }
+        population.add(person).unwrap();
+      }
+      population.reindex();
+      for policies in [&policies[..0], &policies[..]].iter() {
+        let mut expected: Vec<usize> = Vec::new();
+        for person1 in population.iter().filter(|person| person.get_id() % 10 == 0) {
+          for person2 in population.iter() {
+            let factor = Modifiers::for_person(policies, &person1).distance.max(Modifiers::for_person(policies, &person2).distance);
+            let reach = distance * factor;
+            if person1.sqr_distance(&person2, &population.world) < reach * reach {
+              expected.push(person2.get_id());
+            }
+          }
+        }
+        expected.sort();
+        expected.dedup();
+        for cells in [1, 3, 10, 33, 100].iter() {
+          population.set_index(SpatialIndex::Grid);
+          population.fit_index(100.0 / *cells as f32);
+          let mut found: Vec<usize> = population.infect_closeby_single_threaded(policies, &strains).keys().cloned().collect();
+          found.sort();
+          assert_eq!(found, expected, "distance {}, {} cells", distance, cells);
+        }
+        population.set_index(SpatialIndex::KdTree);
+        let mut found: Vec<usize> = population.infect_closeby_single_threaded(policies, &strains).keys().cloned().collect();
+        found.sort();
+        assert_eq!(found, expected, "distance {}, k-d tree", distance);
+      }
+    }
+  }
+
+  #[test]
+  fn changing_the_index_keeps_everybody() {
+    let mut population = Population::new(200.0, 100.0, 1, 1);
+    for index in 0..100 {
+      population.add(Person::new_random(200.0, 100.0, index)).unwrap();
+    }
+    for distance in [10.0, 0.01, 500.0].iter() {
+      population.fit_index(*distance);
+      assert_eq!(population.people_within(0.0, 0.0, 1000.0).len(), 100);
+    }
+    population.set_index(SpatialIndex::KdTree);
+    assert_eq!(population.spatial_index(), SpatialIndex::KdTree);
+    assert_eq!(population.people_within(0.0, 0.0, 1000.0).len(), 100);
+    assert_eq!(population.clone().people_within(0.0, 0.0, 1000.0).len(), 100);
+  }
+
+  #[test]
+  fn walls_block_infections() {
+      let mut virus = Virus::corona();
+      virus.distance = 5.0;
+      virus.infection_rate = 1.0;
+      let mut strains = Strains::new();
+      let strain = strains.add(virus.clone());
+      let mut population = Population::new(10.0, 10.0, 2, 2);
+      population.add_wall(Wall::new(2.5, 0.0, 2.5, 10.0));
+      let mut infected_person = Person::new(2.0, 2.0, 0);
+      infected_person.infect(strain, &virus);
+      population.add(infected_person).unwrap();
+      population.add(Person::new(3.0, 2.0, 1)).unwrap();
+      population.add(Person::new(2.0, 3.0, 2)).unwrap();
+      population.infect_closeby(&[], &strains);
+      for person in population.iter() {
+        let infected = person.get_state() != PersonState::Susceptible;
+        assert_eq!(infected, person.get_id() != 1);
+      }
+  }
+
+  #[test]
+  fn taking_a_person_keeps_the_other_ids() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    for index in 0..20 {
+      population.add(Person::new(index as f32 * 5.0, 50.0, index)).unwrap();
+    }
+    let person = population.take_random().unwrap();
+    assert!(person.get_id() < 20);
+    assert_eq!(population.len(), 19);
+    let mut ids: Vec<usize> = population.iter().map(|person| person.get_id()).collect();
+    ids.sort();
+    let expected: Vec<usize> = (0..20).filter(|id| *id != person.get_id()).collect();
+    assert_eq!(ids, expected);
+    population.add_with_next_id(person);
+    assert_eq!(population.len(), 20);
+    assert!(population.iter().any(|person| person.get_id() == 20));
+  }
+
+  #[test]
+  fn counts_follow_the_changes_of_people() {
+    let mut virus = Virus::corona();
+    virus.incubation_time = 1;
+    virus.recovery_time = 2;
+    virus.mortality_rate = 0.5;
+    let mut strains = Strains::new();
+    let strain = strains.add(virus);
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    for index in 0..10 {
+      let mut person = Person::new(index as f32 * 10.0, 50.0, index);
+      if index < 2 {
+        person.set_state(PersonState::Infectious(strain));
+      }
+      population.add(person).unwrap();
+    }
+    assert_eq!((population.counts().susceptible, population.counts().infectious), (8, 2));
+    population.get_mut(5).unwrap().set_state(PersonState::Recovered(true));
+    population.get_mut(6).unwrap().isolate(1);
+    population.get_mut(7).unwrap().set_state(PersonState::Exposed(strain));
+    population.for_each_mut(|person| person.vaccinate(Protection { infection: 0.0, death: 0.0 }));
+    let counts = population.counts();
+    assert_eq!((counts.susceptible, counts.exposed, counts.recovered, counts.dead), (6, 1, 1, 1));
+    assert_eq!((counts.isolated, counts.vaccinated, counts.total()), (1, 10, 10));
+    // the isolation ends as people age
+    population.update_positions(0.0, &[], &strains);
+    assert_eq!(population.counts().isolated, 0);
+    // and the exposed and infectious people move on to the next state
+    for _ in 0..5 {
+      population.update_positions(0.0, &[], &strains);
+    }
+    assert_eq!(population.counts().recovered, 4);
+    population.remove(0);
+    population.take_random();
+    assert_eq!(population.counts().total(), 8);
+    let mut expected = Counts::default();
+    for person in population.iter() {
+      expected += Counts::of(&person);
+    }
+    assert_eq!(population.counts(), expected);
+  }
+
+  #[test]
+  fn people_stay_out_of_impassable_places() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    population.set_impassable(DensityMap::from_csv("1,0\n0,0").unwrap());
+    for index in 0..50 {
+      population.add(Person::new(index as f32, index as f32, index)).unwrap();
+    }
+    for _ in 0..10 {
+      population.update_positions(10.0, &[], &Strains::new());
+    }
+    for person in population.iter() {
+      assert!(person.position.x < 50.0 && person.position.y < 50.0);
+    }
+  }
+
+  #[test]
+  fn find_people_within_radius() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    for index in 0..100 {
+      population.add(Person::new((index % 10) as f32 * 10.0 + 5.0, (index / 10) as f32 * 10.0 + 5.0, index)).unwrap();
+    }
+    assert_eq!(population.people_within(45.0, 45.0, 1.0).len(), 1);
+    assert_eq!(population.people_within(45.0, 45.0, 10.5).len(), 5);
+    assert_eq!(population.people_within(45.0, 45.0, 30.5).len(), 29);
+    // wraps around the border of the world
+    assert_eq!(population.people_within(0.0, 0.0, 7.5).len(), 4);
+    assert_eq!(population.people_within(50.0, 50.0, 1000.0).len(), 100);
+  }
+
+  #[test]
+  fn find_nearest_person() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    assert!(population.nearest(50.0, 50.0).is_none());
+    population.add(Person::new(10.0, 10.0, 0)).unwrap();
+    population.add(Person::new(60.0, 60.0, 1)).unwrap();
+    assert_eq!(population.nearest(50.0, 50.0).unwrap().get_id(), 1);
+    // wraps around the border of the world
+    assert_eq!(population.nearest(95.0, 95.0).unwrap().get_id(), 0);
+  }
+
+  #[test]
+  fn removed_ids_are_not_reused() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    for index in 0..5 {
+      population.add(Person::new(index as f32 * 20.0, 50.0, index)).unwrap();
+    }
+    assert_eq!(population.remove(4).unwrap().get_id(), 4);
+    assert!(population.remove(7).is_none());
+    assert_eq!(population.add_new(30.0, 30.0).get_id(), 5);
+    let mut ids: Vec<usize> = population.iter().map(|person| person.get_id()).collect();
+    ids.sort();
+    assert_eq!(ids, vec![0, 1, 2, 3, 5]);
+  }
+
+  #[test]
+  fn look_up_people_by_id() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    for index in 0..50 {
+      population.add(Person::new(index as f32 * 2.0, 50.0, index * 3)).unwrap();
+    }
+    // an id that is taken already is refused
+    assert!(population.add(Person::new(0.0, 0.0, 3)).is_err());
+    assert_eq!(population.get(3).unwrap().position.x, 2.0);
+    assert_eq!(population.add_with_next_id(Person::new(0.0, 0.0, 3)), 148);
+    assert_eq!(population.len(), 51);
+    assert_eq!(population.get(148).unwrap().position.x, 0.0);
+    for _ in 0..5 {
+      population.update_positions(10.0, &[], &Strains::new());
+    }
+    population.remove(30);
+    population.remove(33);
+    assert!(population.get(30).is_none());
+    for person in population.iter() {
+      assert_eq!(population.get(person.get_id()).unwrap().position, person.position);
+    }
+    population.get_mut(36).unwrap().years = 200;
+    assert_eq!(population.iter().filter(|person| person.years == 200).count(), 1);
+  }
+
+  #[test]
+  fn index_follows_people_that_come_and_go() {
+    let mut population = Population::new(100.0, 100.0, 10, 10);
+    for index in 0..300 {
+      population.add(Person::new_random(100.0, 100.0, index)).unwrap();
+    }
+    let check = |population: &Population| {
+      for person in population.iter() {
+        let position = &person.position;
+        let found = population.people_within(position.x, position.y, 0.001);
+        assert!(found.iter().any(|other| other.get_id() == person.get_id()));
+      }
+      let everybody = population.people_within(50.0, 50.0, 1000.0);
+      assert_eq!(everybody.len(), population.len());
+    };
+    check(&population);
+    // some people left and came since the last sort
+    for id in (0..300).step_by(7) {
+      population.remove(id);
+    }
+    for _ in 0..20 {
+      population.add_new(25.0, 75.0);
+    }
+    assert!(!population.snapshot.unindexed.is_empty());
+    check(&population);
+    population.update_positions(10.0, &[], &Strains::new());
+    assert!(population.snapshot.unindexed.is_empty());
+    check(&population);
+  }
+
+  #[test]
+  fn infect_people_with_sparse_ids() {
+    let mut virus = Virus::corona();
+    virus.infection_rate = 1.0;
+    let mut strains = Strains::new();
+    let strain = strains.add(virus);
+    let mut population = Population::new(10.0, 10.0, 2, 2);
+    let mut infected_person = Person::new(2.0, 2.0, 7);
+    infected_person.set_state(PersonState::Infectious(strain));
+    population.add(infected_person).unwrap();
+    population.add(Person::new(3.0, 2.0, 42)).unwrap();
+    population.infect_closeby(&[], &strains);
+    assert!(population.iter().all(|person| person.get_state() != PersonState::Susceptible));
+  }
+
+  #[test]
+  fn isolated_people_dont_infect() {
+      let mut virus = Virus::corona();
+      virus.infection_rate = 1.0;
+      let mut strains = Strains::new();
+      let strain = strains.add(virus);
+      let mut population = Population::new(10.0, 10.0, 2, 2);
+      let mut infected_person = Person::new(2.0, 2.0, 0);
+      infected_person.set_state(PersonState::Infectious(strain));
+      infected_person.isolate(10);
+      population.add(infected_person).unwrap();
+      population.add(Person::new(3.0, 2.0, 1)).unwrap();
+      population.infect_closeby(&[], &strains);
+      assert!(population.iter().any(|person| person.get_state() == PersonState::Susceptible));
+  }
+
+  #[test]
+  fn quarantined_people_avert_infections() {
+      let mut virus = Virus::corona();
+      virus.infection_rate = 0.5;
+      let mut strains = Strains::new();
+      let strain = strains.add(virus);
+      let mut population = Population::new(10.0, 10.0, 2, 2);
+      let mut infected_person = Person::new(2.0, 2.0, 0);
+      infected_person.set_state(PersonState::Infectious(strain));
+      infected_person.isolate(10);
+      population.add(infected_person).unwrap();
+      population.add(Person::new(3.0, 2.0, 1)).unwrap();
+      population.add(Person::new(2.0, 3.0, 2)).unwrap();
+      assert_eq!(population.infect_closeby(&[], &strains), 1.0);
+      assert!(population.iter().all(|person| person.get_id() == 0 || person.get_state() == PersonState::Susceptible));
+  }
+
+  #[test]
+  fn averted_infections_are_counted_once_per_contact() {
+      let mut virus = Virus::corona();
+      virus.infection_rate = 1.0;
+      virus.recovery_time = 20;
+      let mut strains = Strains::new();
+      let strain = strains.add(virus);
+      let mut population = Population::new(10.0, 10.0, 2, 2);
+      let mut infected_person = Person::new(2.0, 2.0, 0);
+      infected_person.set_state(PersonState::Infectious(strain));
+      infected_person.isolate(10);
+      population.add(infected_person).unwrap();
+      population.add(Person::new(3.0, 2.0, 1)).unwrap();
+      population.add(Person::new(2.0, 3.0, 2)).unwrap();
+      let mut averted = 0.0;
+      for _ in 0..8 {
+        averted += population.infect_closeby(&[], &strains);
+        population.update_positions(0.0, &[], &strains);
+      }
+      assert_eq!(averted, 2.0);
+  }
+}
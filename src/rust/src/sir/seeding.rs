@@ -0,0 +1,81 @@
+use wasm_bindgen::prelude::*;
+
+/// How the people that are seeded are chosen
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SeedSelection {
+    /// Random people all over the world
+    Random,
+    /// The people closest to the location (x, y) of the `Seeding`
+    Cluster,
+    /// The people with the ids given to `Seeding::set_ids`, in that order
+    Ids,
+}
+
+/// Describes the state of the population at the start of the simulation.
+/// The seeded people always get their state, there is no chance involved.
+/// When more people are selected than needed, the first ones become infectious,
+/// the next ones exposed and the last ones immune.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Seeding {
+    pub infectious: usize,
+    pub exposed: usize,
+    pub immune: usize,
+    pub selection: SeedSelection,
+    pub x: f32,
+    pub y: f32,
+    ids: Vec<usize>,
+}
+
+#[wasm_bindgen]
+impl Seeding {
+    /// Seeds nobody, set the amounts and the selection afterwards
+    pub fn new() -> Seeding {
+        Seeding {
+            infectious: 0,
+            exposed: 0,
+            immune: 0,
+            selection: SeedSelection::Random,
+            x: 0.0,
+            y: 0.0,
+            ids: Vec::new(),
+        }
+    }
+    /// A single infectious person, the one with id 0
+    pub fn patient_zero() -> Seeding {
+        let mut seeding = Seeding::new();
+        seeding.infectious = 1;
+        seeding.set_ids(vec![0]);
+        seeding
+    }
+    /// Seeds a fraction of a population of `population_size` people
+    pub fn fractions(population_size: usize, infectious: f32, exposed: f32, immune: f32) -> Seeding {
+        let count = |fraction: f32| (fraction.clamp(0.0, 1.0) * population_size as f32).round() as usize;
+        let mut seeding = Seeding::new();
+        seeding.infectious = count(infectious);
+        seeding.exposed = count(exposed);
+        seeding.immune = count(immune);
+        seeding
+    }
+    /// Uses the given ids as the selection
+    pub fn set_ids(&mut self, ids: Vec<usize>) {
+        self.ids = ids;
+        self.selection = SeedSelection::Ids;
+    }
+    pub fn total(&self) -> usize {
+        self.infectious + self.exposed + self.immune
+    }
+}
+
+impl Seeding {
+    pub fn ids(&self) -> &[usize] {
+        &self.ids
+    }
+}
+
+impl Default for Seeding {
+    fn default() -> Self {
+        Seeding::new()
+    }
+}
@@ -0,0 +1,137 @@
+use super::person::{Person, PersonState};
+use super::population::Population;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::prelude::*;
+
+/// Who gets tested
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TestSelection {
+    /// Random people that are not known to be infected
+    Random,
+    /// Only people with symptoms, i.e. infectious people that are not detected yet
+    Symptomatic,
+}
+
+/// Tests a number of people every tick. A test detects an infection with a chance
+/// equal to the sensitivity and the result comes in after `delay` ticks. Positive
+/// people are isolated during `isolation_period` ticks.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Testing {
+    pub tests_per_tick: usize,
+    pub selection: TestSelection,
+    pub sensitivity: f32,
+    pub delay: usize,
+    pub isolation_period: usize,
+    // ids of the people that tested positive, by the tick their result comes in
+    pending: Vec<(usize, usize)>,
+}
+
+#[wasm_bindgen]
+impl Testing {
+    pub fn new(tests_per_tick: usize, selection: TestSelection) -> Testing {
+        Testing {
+            tests_per_tick,
+            selection,
+            sensitivity: 0.8,
+            delay: 2,
+            isolation_period: 140,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Testing {
+    fn can_be_tested(person: &Person) -> bool {
+        !person.is_detected() && person.get_state() != PersonState::Recovered(true)
+    }
+    fn is_infected(person: &Person) -> bool {
+        matches!(person.get_state(), PersonState::Exposed(_) | PersonState::Infectious(_))
+    }
+    /// Tests people and isolates the people whose positive result came in.
+    /// Returns the ids of the people that were detected during this tick.
+    pub fn update(&mut self, tick: usize, population: &mut Population) -> Vec<usize> {
+        let mut rng = rand::thread_rng();
+        let candidates: Vec<Person> = population
+            .iter()
+            .filter(Testing::can_be_tested)
+            .filter(|person| match self.selection {
+                TestSelection::Random => true,
+                TestSelection::Symptomatic => matches!(person.get_state(), PersonState::Infectious(_)),
+            })
+            .collect();
+        let pending: HashSet<usize> = self.pending.iter().map(|(_, id)| *id).collect();
+        for person in candidates.choose_multiple(&mut rng, self.tests_per_tick) {
+            if Testing::is_infected(person) && !pending.contains(&person.get_id()) && rng.gen_range(0.0, 1.0) < self.sensitivity {
+                self.pending.push((tick + self.delay, person.get_id()));
+            }
+        }
+        let results: HashMap<usize, usize> = self
+            .pending
+            .iter()
+            .filter(|(ready, _)| *ready <= tick)
+            .map(|(ready, id)| (*id, *ready))
+            .collect();
+        self.pending.retain(|(ready, _)| *ready > tick);
+        if results.is_empty() {
+            return Vec::new();
+        }
+        let mut detected = Vec::new();
+        for id in results.keys() {
+            if let Some(mut person) = population.get_mut(*id) {
+                person.detect();
+                person.isolate(self.isolation_period);
+                detected.push(*id);
+            }
+        }
+        detected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sir::virus::{Strains, Virus};
+
+    fn population() -> Population {
+        let strain = Strains::new().add(Virus::corona());
+        let mut population = Population::new(100.0, 100.0, 10, 10);
+        for index in 0..10 {
+            let mut person = Person::new(index as f32 * 10.0, 50.0, index);
+            if index < 3 {
+                person.set_state(PersonState::Infectious(strain));
+            }
+            population.add(person).unwrap();
+        }
+        population
+    }
+
+    #[test]
+    fn positive_people_are_isolated_after_the_delay() {
+        let mut population = population();
+        let mut testing = Testing::new(10, TestSelection::Symptomatic);
+        testing.sensitivity = 1.0;
+        testing.delay = 1;
+        assert!(testing.update(0, &mut population).is_empty());
+        assert!(population.iter().all(|person| !person.is_isolated()));
+        let mut detected = testing.update(1, &mut population);
+        detected.sort();
+        assert_eq!(detected, vec![0, 1, 2]);
+        for person in population.iter() {
+            assert_eq!(person.is_isolated(), person.get_id() < 3);
+            assert_eq!(person.is_detected(), person.get_id() < 3);
+        }
+    }
+
+    #[test]
+    fn insensitive_tests_miss_cases() {
+        let mut population = population();
+        let mut testing = Testing::new(10, TestSelection::Random);
+        testing.sensitivity = 0.0;
+        testing.delay = 0;
+        assert!(testing.update(0, &mut population).is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use super::person::Person;
+use super::population::Population;
+use std::collections::{HashSet, VecDeque};
+use wasm_bindgen::prelude::*;
+
+/// Digital contact tracing. App users remember the other app users they were close to
+/// during the last `window` ticks. When a user is detected, those contacts are
+/// quarantined `delay` ticks later during `quarantine_period` ticks.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Tracing {
+    /// Fraction of the people that uses the app
+    pub adoption: f32,
+    pub distance: f32,
+    pub window: usize,
+    pub delay: usize,
+    pub quarantine_period: usize,
+    // pairs of ids of the app users that were close to each other, by tick
+    contacts: VecDeque<(usize, Vec<(usize, usize)>)>,
+    // ids of the contacts to quarantine, by the tick they get the message
+    pending: Vec<(usize, usize)>,
+}
+
+#[wasm_bindgen]
+impl Tracing {
+    pub fn new(adoption: f32) -> Tracing {
+        Tracing {
+            adoption,
+            distance: 10.0,
+            window: 50,
+            delay: 1,
+            quarantine_period: 140,
+            contacts: VecDeque::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Tracing {
+    /// Like with policies, the people with the lowest reluctance use the app
+    pub fn uses_app(&self, person: &Person) -> bool {
+        person.get_reluctance() < self.adoption
+    }
+    /// Remembers who is close to whom during tick `tick`
+    pub fn record(&mut self, tick: usize, population: &Population) {
+        let mut pairs = Vec::new();
+        for person in population.iter().filter(|person| self.uses_app(person)) {
+            let position = &person.position;
+            for other in population.people_within(position.x, position.y, self.distance) {
+                if other.get_id() > person.get_id() && self.uses_app(&other) {
+                    pairs.push((person.get_id(), other.get_id()));
+                }
+            }
+        }
+        self.contacts.push_back((tick, pairs));
+        while let Some((recorded, _)) = self.contacts.front() {
+            if recorded + self.window > tick {
+                break;
+            }
+            self.contacts.pop_front();
+        }
+    }
+    /// Looks up the contacts of the `detected` people and quarantines the contacts
+    /// whose message came in. Returns the number of people that went into quarantine.
+    pub fn trace(&mut self, tick: usize, detected: &[usize], population: &mut Population) -> usize {
+        let detected: HashSet<usize> = detected.iter().cloned().collect();
+        if !detected.is_empty() {
+            for (_, pairs) in self.contacts.iter() {
+                for (id1, id2) in pairs.iter() {
+                    if detected.contains(id1) {
+                        self.pending.push((tick + self.delay, *id2));
+                    }
+                    if detected.contains(id2) {
+                        self.pending.push((tick + self.delay, *id1));
+                    }
+                }
+            }
+        }
+        let notified: HashSet<usize> = self
+            .pending
+            .iter()
+            .filter(|(ready, _)| *ready <= tick)
+            .map(|(_, id)| *id)
+            .collect();
+        self.pending.retain(|(ready, _)| *ready > tick);
+        if notified.is_empty() {
+            return 0;
+        }
+        let mut quarantined = 0;
+        for id in notified {
+            match population.get_mut(id) {
+                Some(mut person) if !person.is_detected() => {
+                    person.isolate(self.quarantine_period);
+                    quarantined += 1;
+                }
+                _ => {}
+            }
+        }
+        quarantined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn population() -> Population {
+        let mut population = Population::new(100.0, 100.0, 10, 10);
+        for index in 0..3 {
+            population.add(Person::new(50.0 + index as f32, 50.0, index)).unwrap();
+        }
+        population.add(Person::new(10.0, 10.0, 3)).unwrap();
+        population
+    }
+
+    #[test]
+    fn contacts_of_cases_are_quarantined() {
+        let mut population = population();
+        let mut tracing = Tracing::new(1.0);
+        tracing.delay = 1;
+        tracing.record(0, &population);
+        assert_eq!(tracing.trace(0, &[0], &mut population), 0);
+        assert_eq!(tracing.trace(1, &[], &mut population), 2);
+        for person in population.iter() {
+            assert_eq!(person.is_quarantined(), person.get_id() == 1 || person.get_id() == 2);
+        }
+    }
+
+    #[test]
+    fn only_app_users_are_traced() {
+        let mut population = population();
+        let mut tracing = Tracing::new(0.0);
+        tracing.delay = 0;
+        tracing.record(0, &population);
+        assert_eq!(tracing.trace(0, &[0], &mut population), 0);
+    }
+
+    #[test]
+    fn old_contacts_are_forgotten() {
+        let mut population = population();
+        let mut tracing = Tracing::new(1.0);
+        tracing.delay = 0;
+        tracing.window = 5;
+        tracing.record(0, &population);
+        let nobody = Population::new(100.0, 100.0, 10, 10);
+        for tick in 1..4 {
+            tracing.record(tick, &nobody);
+        }
+        assert_eq!(tracing.trace(4, &[0], &mut population), 2);
+        let mut population = self::population();
+        tracing.record(5, &nobody);
+        assert_eq!(tracing.trace(5, &[1], &mut population), 0);
+    }
+}
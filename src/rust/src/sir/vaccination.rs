@@ -0,0 +1,159 @@
+use super::person::{Person, PersonState, Protection};
+use super::population::Population;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use wasm_bindgen::prelude::*;
+
+/// Who gets vaccinated first
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VaccinationStrategy {
+    Random,
+    OldestFirst,
+    /// People with the most people around them first
+    HighestContact,
+    /// People close to an infectious person first, nobody else
+    Ring,
+}
+
+/// Vaccinates a number of susceptible people every tick, starting at tick `start`
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Campaign {
+    pub start: usize,
+    pub per_tick: usize,
+    pub strategy: VaccinationStrategy,
+    /// Fraction of the infections the vaccine prevents
+    pub efficacy_infection: f32,
+    /// Fraction of the deaths the vaccine prevents
+    pub efficacy_death: f32,
+    /// Distance at which contacts are counted for the highest contact and ring strategies
+    pub contact_distance: f32,
+}
+
+#[wasm_bindgen]
+impl Campaign {
+    pub fn new(start: usize, per_tick: usize, strategy: VaccinationStrategy) -> Campaign {
+        Campaign {
+            start,
+            per_tick,
+            strategy,
+            efficacy_infection: 0.9,
+            efficacy_death: 0.95,
+            contact_distance: 30.0,
+        }
+    }
+}
+
+impl Campaign {
+    pub fn protection(&self) -> Protection {
+        Protection {
+            infection: self.efficacy_infection,
+            death: self.efficacy_death,
+        }
+    }
+    fn is_eligible(person: &Person) -> bool {
+        person.get_state() == PersonState::Susceptible && !person.is_vaccinated()
+    }
+    /// The ids of the people to vaccinate during tick `tick`
+    pub fn select(&self, tick: usize, population: &Population) -> Vec<usize> {
+        if tick < self.start || self.per_tick == 0 {
+            return Vec::new();
+        }
+        let eligible = population.iter().filter(Campaign::is_eligible);
+        match self.strategy {
+            VaccinationStrategy::Random => {
+                let ids: Vec<usize> = eligible.map(|person| person.get_id()).collect();
+                ids.choose_multiple(&mut rand::thread_rng(), self.per_tick).cloned().collect()
+            }
+            VaccinationStrategy::OldestFirst => {
+                let mut people: Vec<(u32, usize)> = eligible.map(|person| (person.years, person.get_id())).collect();
+                people.sort_by(|a, b| b.cmp(a));
+                people.iter().take(self.per_tick).map(|(_, id)| *id).collect()
+            }
+            VaccinationStrategy::HighestContact => {
+                let mut people: Vec<(usize, usize)> = eligible
+                    .map(|person| {
+                        let position = &person.position;
+                        let contacts = population.people_within(position.x, position.y, self.contact_distance).len();
+                        (contacts, person.get_id())
+                    })
+                    .collect();
+                people.sort_by(|a, b| b.cmp(a));
+                people.iter().take(self.per_tick).map(|(_, id)| *id).collect()
+            }
+            VaccinationStrategy::Ring => {
+                let mut ring = Vec::new();
+                let mut seen = HashSet::new();
+                for case in population.iter() {
+                    if let PersonState::Infectious(_) = case.get_state() {
+                        let position = &case.position;
+                        for person in population.people_within(position.x, position.y, self.contact_distance) {
+                            if Campaign::is_eligible(&person) && seen.insert(person.get_id()) {
+                                ring.push(person.get_id());
+                            }
+                        }
+                    }
+                }
+                ring.shuffle(&mut rand::thread_rng());
+                ring.truncate(self.per_tick);
+                ring
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sir::virus::{Strains, Virus};
+
+    fn population() -> Population {
+        let mut population = Population::new(100.0, 100.0, 10, 10);
+        for index in 0..10 {
+            let mut person = Person::new(index as f32 * 10.0 + 5.0, 5.0, index);
+            person.years = index as u32;
+            population.add(person).unwrap();
+        }
+        population
+    }
+
+    #[test]
+    fn campaign_waits_for_start() {
+        let campaign = Campaign::new(5, 3, VaccinationStrategy::Random);
+        assert!(campaign.select(4, &population()).is_empty());
+        assert_eq!(campaign.select(5, &population()).len(), 3);
+    }
+
+    #[test]
+    fn oldest_are_vaccinated_first() {
+        let campaign = Campaign::new(0, 3, VaccinationStrategy::OldestFirst);
+        assert_eq!(campaign.select(0, &population()), vec![9, 8, 7]);
+    }
+
+    #[test]
+    fn people_with_most_contacts_are_vaccinated_first() {
+        let mut population = population();
+        population.add(Person::new(55.0, 55.0, 10)).unwrap();
+        population.add(Person::new(56.0, 55.0, 11)).unwrap();
+        population.add(Person::new(55.0, 56.0, 12)).unwrap();
+        let mut campaign = Campaign::new(0, 3, VaccinationStrategy::HighestContact);
+        campaign.contact_distance = 5.0;
+        let mut selected = campaign.select(0, &population);
+        selected.sort();
+        assert_eq!(selected, vec![10, 11, 12]);
+    }
+
+    #[test]
+    fn ring_vaccination_around_cases() {
+        let mut population = Population::new(100.0, 100.0, 10, 10);
+        let mut case = Person::new(50.0, 50.0, 0);
+        case.set_state(PersonState::Infectious(Strains::new().add(Virus::corona())));
+        population.add(case).unwrap();
+        population.add(Person::new(55.0, 50.0, 1)).unwrap();
+        population.add(Person::new(80.0, 50.0, 2)).unwrap();
+        let mut campaign = Campaign::new(0, 10, VaccinationStrategy::Ring);
+        campaign.contact_distance = 10.0;
+        assert_eq!(campaign.select(0, &population), vec![1]);
+    }
+}
@@ -4,6 +4,7 @@ use wasm_bindgen::prelude::*;
 #[derive(Clone, Debug, PartialEq)]
 pub struct Virus {
     pub distance: f32,
+    pub incubation_time: usize, // days before an exposed person becomes infectious
     pub recovery_time: usize, // days
     pub infection_rate: f32,  // between 0 and 1
     pub mortality_rate: f32,  // between 0 and 1
@@ -14,9 +15,50 @@ impl Virus {
     pub fn corona() -> Virus {
         Virus {
             distance: 10.0,
+            incubation_time: 0,
             recovery_time: 100,
             infection_rate: 0.7,
             mortality_rate: 0.05,
         }
     }
 }
+
+/// The id of a virus in the `Strains` of a world
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Strain(u32);
+
+/// Every virus in a world, infected people carry the strain of their virus instead of a copy of it
+#[derive(Clone, Debug, Default)]
+pub struct Strains {
+    viruses: Vec<Virus>,
+}
+
+impl Strains {
+    pub fn new() -> Strains {
+        Strains { viruses: Vec::new() }
+    }
+    pub fn add(&mut self, virus: Virus) -> Strain {
+        self.viruses.push(virus);
+        Strain(self.viruses.len() as u32 - 1)
+    }
+    /// The strain of `virus`, it is added when there is no strain like it yet
+    pub fn find_or_add(&mut self, virus: &Virus) -> Strain {
+        match self.viruses.iter().position(|other| other == virus) {
+            Some(index) => Strain(index as u32),
+            None => self.add(virus.clone()),
+        }
+    }
+    pub fn get(&self, strain: Strain) -> &Virus {
+        &self.viruses[strain.0 as usize]
+    }
+    /// Changes to the virus reach everybody that carries the strain
+    pub fn get_mut(&mut self, strain: Strain) -> &mut Virus {
+        &mut self.viruses[strain.0 as usize]
+    }
+    pub fn len(&self) -> usize {
+        self.viruses.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.viruses.is_empty()
+    }
+}
@@ -0,0 +1,85 @@
+use super::person::Location;
+
+/// A straight barrier inside the world. People cannot walk through it and
+/// the virus cannot be transmitted across it.
+#[derive(Clone, Debug)]
+pub struct Wall {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Wall {
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Wall {
+        Wall {
+            start: Location { x: x1, y: y1 },
+            end: Location { x: x2, y: y2 },
+        }
+    }
+    /// Splits a closed polygon, given as a flat list of `x, y` coordinates,
+    /// into the walls along its edges.
+    pub fn polygon(points: &[f32]) -> Vec<Wall> {
+        let corners: Vec<(f32, f32)> = points.chunks_exact(2).map(|c| (c[0], c[1])).collect();
+        let mut walls = Vec::with_capacity(corners.len());
+        if corners.len() < 2 {
+            return walls;
+        }
+        for index in 0..corners.len() {
+            let (x1, y1) = corners[index];
+            let (x2, y2) = corners[(index + 1) % corners.len()];
+            walls.push(Wall::new(x1, y1, x2, y2));
+        }
+        walls
+    }
+    fn orientation(a: &Location, b: &Location, c: &Location) -> f32 {
+        (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+    }
+    /// Returns true if the path going from `from` to `to` crosses this wall.
+    pub fn crosses(&self, from: &Location, to: &Location) -> bool {
+        let d1 = Wall::orientation(&self.start, &self.end, from);
+        let d2 = Wall::orientation(&self.start, &self.end, to);
+        let d3 = Wall::orientation(from, to, &self.start);
+        let d4 = Wall::orientation(from, to, &self.end);
+        // touching the wall counts as crossing it, so nobody can stand on a wall
+        d1 * d2 <= 0.0 && d3 * d4 <= 0.0 && (d1 != 0.0 || d2 != 0.0)
+    }
+}
+
+/// Returns true if any of the walls lies on the path from `from` to `to`.
+pub fn is_blocked(walls: &[Wall], from: &Location, to: &Location) -> bool {
+    walls.iter().any(|wall| wall.crosses(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_through_wall_is_blocked() {
+        let wall = Wall::new(5.0, 0.0, 5.0, 10.0);
+        let from = Location { x: 2.0, y: 5.0 };
+        let to = Location { x: 8.0, y: 5.0 };
+        assert!(wall.crosses(&from, &to));
+        assert!(wall.crosses(&to, &from));
+    }
+
+    #[test]
+    fn path_next_to_wall_is_free() {
+        let wall = Wall::new(5.0, 0.0, 5.0, 10.0);
+        let from = Location { x: 2.0, y: 12.0 };
+        let to = Location { x: 8.0, y: 12.0 };
+        assert!(!wall.crosses(&from, &to));
+        let parallel_to = Location { x: 2.0, y: 0.0 };
+        assert!(!wall.crosses(&from, &parallel_to));
+    }
+
+    #[test]
+    fn polygon_is_closed() {
+        let walls = Wall::polygon(&[0.0, 0.0, 10.0, 0.0, 10.0, 10.0]);
+        assert_eq!(walls.len(), 3);
+        assert_eq!(walls[2].end.x, 0.0);
+        assert_eq!(walls[2].end.y, 0.0);
+        let inside = Location { x: 7.0, y: 3.0 };
+        let outside = Location { x: 3.0, y: 7.0 };
+        assert!(is_blocked(&walls, &inside, &outside));
+    }
+}
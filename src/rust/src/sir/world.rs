@@ -1,11 +1,28 @@
-use super::person::{Person, PersonState};
-use super::virus::Virus;
+use super::demographics::Demographics;
+use super::density::DensityMap;
+use super::geometry::Dimensions;
+use super::neighbours::SpatialIndex;
+use super::person::{Location, Person, PersonState, Protection};
+use super::virus::{Strain, Strains, Virus};
+use super::policy::{Controller, Modifiers, Policy};
 use super::population::Population;
-use std::iter::Flatten;
-use std::slice::Iter;
+use super::seeding::{SeedSelection, Seeding};
+use super::testing::Testing;
+use super::tracing::Tracing;
+use super::vaccination::Campaign;
+use super::wall::Wall;
+use rand::distributions::{Distribution, Normal};
+use rand::rngs::ThreadRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::vec_deque;
+use std::collections::{HashMap, VecDeque};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::{JsCast, JsValue};
 
+// The number of ticks the history keeps by default, long sessions in the browser would keep growing it
+const HISTORY_LIMIT: usize = 10_000;
+
 #[wasm_bindgen]
 extern "C" {
     // Use `js_namespace` here to bind `console.log(..)` instead of just
@@ -19,21 +36,103 @@ extern "C" {
 pub enum PopulationDistribution {
     Random,
     Grid,
+    /// Follows the density map of the `DistributionSettings`, random without a map
+    Density,
+    /// Gaussian blobs around a number of random centers
+    Clustered,
+    /// Households of people sharing the same home
+    Households,
+}
+
+/// Extra settings for placing the population in a new world
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct DistributionSettings {
+    density: Option<DensityMap>,
+    /// Places without any density can't be entered
+    pub density_blocks_movement: bool,
+    /// Number of clusters for the clustered distribution
+    pub cluster_count: usize,
+    /// Standard deviation of the distance of people to the center of their cluster
+    pub cluster_spread: f32,
+    /// Number of people living together for the households distribution
+    pub household_size: usize,
+}
+
+#[wasm_bindgen]
+impl DistributionSettings {
+    pub fn new() -> DistributionSettings {
+        DistributionSettings {
+            density: None,
+            density_blocks_movement: false,
+            cluster_count: 5,
+            cluster_spread: 50.0,
+            household_size: 4,
+        }
+    }
+    pub fn set_density_map(&mut self, density: DensityMap) {
+        self.density = Some(density);
+    }
+}
+
+impl Default for DistributionSettings {
+    fn default() -> Self {
+        DistributionSettings::new()
+    }
 }
 
 #[wasm_bindgen]
+#[derive(Copy, Clone, Debug)]
 pub struct Stats {
     pub susceptable: f32,
+    pub exposed: f32,
     pub infected: f32,
     pub recovered: f32,
+    /// People that got a vaccine, whatever their state is
+    pub vaccinated: f32,
+    /// People that ever got infected, the true cases
+    pub cases: f32,
+    /// People that ever tested positive, compare with `cases` to see the under-reporting
+    pub detected: f32,
+    pub isolated: f32,
+    /// Expected infections that quarantined contacts would have caused so far, every person counts once
+    pub averted: f32,
+    /// People that got infected during the last tick
+    pub incidence: f32,
 }
 
 #[wasm_bindgen]
 pub struct World {
-    width: f32,
-    height: f32,
+    dimensions: Dimensions,
     move_speed: f32,
-    population: Population
+    // every virus in this world, the people carry the strain of theirs
+    strains: Strains,
+    // the strain of the virus of this world, other strains come with people from other worlds
+    strain: Strain,
+    // the neighbour index is still fitted to the old distance of the virus
+    distance_changed: bool,
+    population: Population,
+    tick: usize,
+    policies: Vec<Policy>,
+    campaigns: Vec<Campaign>,
+    testing: Option<Testing>,
+    tracing: Option<Tracing>,
+    averted: f32,
+    controller: Option<Controller>,
+    demographics: Option<Demographics>,
+    births: usize,
+    deaths: usize,
+    // the records of the last `history_limit` ticks
+    history: VecDeque<Record>,
+    history_limit: usize,
+}
+
+/// The stats of a single tick, with the indexes of the policies that were active
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub tick: usize,
+    pub stats: Stats,
+    pub active_policies: Vec<usize>,
 }
 
 #[wasm_bindgen]
@@ -46,18 +145,40 @@ impl World {
         virus: Virus,
         distribution: PopulationDistribution,
     ) -> World {
-        let max_dist = virus.distance;
-        let mut num_grid_width: usize = ((width as f32) / max_dist).floor() as usize;
-        let mut num_grid_height: usize = ((height as f32) / max_dist).floor() as usize;
-        if num_grid_width > 1000 {
-            num_grid_width = 1000;
-        }
-        if num_grid_height > 1000 {
-            num_grid_height = 1000;
-        }
-        let mut population = Population::new(width as f32, height as f32, num_grid_width, num_grid_height);
+        World::new_with_settings(population_size, width, height, virus, distribution, DistributionSettings::new())
+    }
+    /// Constructs a new World, using `settings` to fine tune the distribution of the people.
+    /// The person with id 0 is infectious, use `seed` to start differently.
+    pub fn new_with_settings(
+        population_size: usize,
+        width: f32,
+        height: f32,
+        virus: Virus,
+        distribution: PopulationDistribution,
+        settings: DistributionSettings,
+    ) -> World {
+        let dimensions = Dimensions::new(width, height);
+        let mut population = Population::new(width, height, 1, 1);
+        population.fit_index(virus.distance);
+        let mut rng = rand::thread_rng();
+        let random_location = |rng: &mut ThreadRng| match &settings.density {
+            Some(density) => density.random_location(width, height),
+            None => Location { x: rng.gen_range(0.0, width), y: rng.gen_range(0.0, height) },
+        };
+        let centers: Vec<Location> = match distribution {
+            PopulationDistribution::Clustered => {
+                (0..settings.cluster_count.max(1)).map(|_| random_location(&mut rng)).collect()
+            }
+            PopulationDistribution::Households => {
+                let household_size = settings.household_size.max(1);
+                let households = population_size.div_ceil(household_size);
+                (0..households).map(|_| random_location(&mut rng)).collect()
+            }
+            _ => Vec::new(),
+        };
+        let spread = Normal::new(0.0, settings.cluster_spread.max(0.0) as f64);
         for index in 0..population_size {
-            let mut person = match distribution {
+            let person = match distribution {
                 PopulationDistribution::Random => {
                     Person::new_random(width, height, index as usize)
                 }
@@ -70,23 +191,208 @@ impl World {
                         index / (grid_width as usize) * ((height as f32 / grid_height) as usize);
                     Person::new(x as f32, y as f32, index as usize)
                 }
+                PopulationDistribution::Density => match &settings.density {
+                    Some(density) => {
+                        let location = density.random_location(width, height);
+                        Person::new(location.x, location.y, index)
+                    }
+                    None => Person::new_random(width, height, index),
+                },
+                PopulationDistribution::Clustered => {
+                    let center = &centers[rng.gen_range(0, centers.len())];
+                    let x = center.x + spread.sample(&mut rng) as f32;
+                    let y = center.y + spread.sample(&mut rng) as f32;
+                    let location = dimensions.wrap(&Location { x, y });
+                    Person::new(location.x, location.y, index)
+                }
+                PopulationDistribution::Households => {
+                    let home = &centers[index / settings.household_size.max(1)];
+                    Person::new(home.x, home.y, index)
+                }
             };
-            if index == 0 {
-                person.infect(virus.clone());
+            population.add_with_next_id(person);
+        }
+        if settings.density_blocks_movement {
+            if let Some(density) = settings.density {
+                population.set_impassable(density);
             }
-            population.add(person);
         }
 
-        World {
+        let mut strains = Strains::new();
+        let strain = strains.add(virus);
+        let mut world = World {
             population,
-            width,
-            height,
-            move_speed: 5.0
+            dimensions,
+            move_speed: 5.0,
+            strains,
+            strain,
+            distance_changed: false,
+            tick: 0,
+            policies: Vec::new(),
+            campaigns: Vec::new(),
+            testing: None,
+            tracing: None,
+            averted: 0.0,
+            controller: None,
+            demographics: None,
+            births: 0,
+            deaths: 0,
+            history: VecDeque::new(),
+            history_limit: HISTORY_LIMIT,
+        };
+        world.seed(&Seeding::patient_zero());
+        world
+    }
+    /// Resets everybody to susceptible and then seeds the people chosen by `seeding`
+    pub fn seed(&mut self, seeding: &Seeding) {
+        let total = seeding.total();
+        let chosen: Vec<usize> = match seeding.selection {
+            SeedSelection::Random => {
+                let ids: Vec<usize> = self.population.iter().map(|person| person.get_id()).collect();
+                ids.choose_multiple(&mut rand::thread_rng(), total).cloned().collect()
+            }
+            SeedSelection::Cluster => {
+                let center = Location { x: seeding.x, y: seeding.y };
+                let mut people: Vec<(f32, usize)> = self.population.iter()
+                    .map(|person| (center.sqr_distance(&person.position, &self.dimensions), person.get_id()))
+                    .collect();
+                people.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                people.iter().take(total).map(|(_, id)| *id).collect()
+            }
+            SeedSelection::Ids => seeding.ids().iter().take(total).cloned().collect(),
+        };
+        let mut states: HashMap<usize, PersonState> = HashMap::new();
+        for (rank, id) in chosen.into_iter().enumerate() {
+            let state = if rank < seeding.infectious {
+                PersonState::Infectious(self.strain)
+            } else if rank < seeding.infectious + seeding.exposed {
+                PersonState::Exposed(self.strain)
+            } else {
+                PersonState::Recovered(false)
+            };
+            states.insert(id, state);
         }
+        self.population.for_each_mut(|person| {
+            let state = states.remove(&person.get_id()).unwrap_or(PersonState::Susceptible);
+            person.set_state(state);
+        });
     }
     pub fn config(&mut self, move_speed: f32) {
         self.move_speed = move_speed;
     }
+    pub fn set_move_speed(&mut self, move_speed: f32) {
+        self.move_speed = move_speed;
+    }
+    /// Uses `threads` threads for large populations, 0 uses all available threads
+    pub fn set_threads(&mut self, threads: usize) -> Result<(), JsValue> {
+        self.use_threads(threads).map_err(|error| JsValue::from_str(&error))
+    }
+    /// Makes the movement of the people the same on every run with the same seed,
+    /// infections, testing, vaccination, births and deaths stay random
+    pub fn set_seed(&mut self, seed: u32) {
+        self.population.set_seed(u64::from(seed));
+    }
+    /// How people look for the people near them, the k-d tree adapts to where the people are, the cells of the grid don't
+    pub fn set_spatial_index(&mut self, index: SpatialIndex) {
+        self.population.set_index(index);
+    }
+    pub fn get_spatial_index(&self) -> SpatialIndex {
+        self.population.spatial_index()
+    }
+    pub fn get_move_speed(&self) -> f32 {
+        self.move_speed
+    }
+    /// The virus as it is now, changes to it reach everybody that carries it
+    pub fn get_virus(&self) -> Virus {
+        self.strains.get(self.strain).clone()
+    }
+    pub fn set_distance(&mut self, distance: f32) {
+        self.strains.get_mut(self.strain).distance = distance;
+        self.distance_changed = true;
+    }
+    pub fn set_infection_rate(&mut self, infection_rate: f32) {
+        self.strains.get_mut(self.strain).infection_rate = infection_rate;
+    }
+    pub fn set_recovery_time(&mut self, recovery_time: usize) {
+        self.strains.get_mut(self.strain).recovery_time = recovery_time;
+    }
+    pub fn set_mortality_rate(&mut self, mortality_rate: f32) {
+        self.strains.get_mut(self.strain).mortality_rate = mortality_rate;
+    }
+    /// The id of the person closest to (x, y)
+    pub fn nearest_person(&self, x: f32, y: f32) -> Option<usize> {
+        self.population.nearest(x, y).map(|person| person.get_id())
+    }
+    /// Infects the susceptible people within `radius` of (x, y), returns how many got infected
+    pub fn infect_within(&mut self, x: f32, y: f32, radius: f32) -> usize {
+        let strain = self.strain;
+        self.update_within(x, y, radius, |person| {
+            if person.get_state() != PersonState::Susceptible {
+                return false;
+            }
+            person.set_state(PersonState::Infectious(strain));
+            true
+        })
+    }
+    /// Makes the susceptible people within `radius` of (x, y) immune
+    pub fn immunise_within(&mut self, x: f32, y: f32, radius: f32) -> usize {
+        self.update_within(x, y, radius, |person| {
+            if person.get_state() != PersonState::Susceptible {
+                return false;
+            }
+            person.set_state(PersonState::Recovered(false));
+            true
+        })
+    }
+    /// Vaccinates the living people within `radius` of (x, y) that didn't get a vaccine yet
+    pub fn vaccinate_within(&mut self, x: f32, y: f32, radius: f32, efficacy: f32) -> usize {
+        self.update_within(x, y, radius, |person| {
+            if person.is_vaccinated() || person.get_state() == PersonState::Recovered(true) {
+                return false;
+            }
+            person.vaccinate(Protection {
+                infection: efficacy,
+                death: efficacy,
+            });
+            true
+        })
+    }
+    /// Removes the people within `radius` of (x, y) from the world
+    pub fn remove_within(&mut self, x: f32, y: f32, radius: f32) -> usize {
+        let ids: Vec<usize> = self.population.people_within(x, y, radius).iter().map(|person| person.get_id()).collect();
+        for id in ids.iter() {
+            self.population.remove(*id);
+        }
+        ids.len()
+    }
+    /// Adds a susceptible person at (x, y), returns the id of the new person
+    pub fn add_person(&mut self, x: f32, y: f32) -> usize {
+        let location = self.dimensions.wrap(&Location { x, y });
+        self.population.add_new(location.x, location.y).get_id()
+    }
+    /// Lets people be born and die of other causes than the virus
+    pub fn set_demographics(&mut self, demographics: Demographics) {
+        self.demographics = Some(demographics);
+    }
+    /// The number of people that were born so far
+    pub fn get_births(&self) -> usize {
+        self.births
+    }
+    /// The number of people that died of other causes than the virus so far
+    pub fn get_deaths(&self) -> usize {
+        self.deaths
+    }
+    /// Places a wall from (x1, y1) to (x2, y2) that people can't cross
+    /// and the virus can't spread through.
+    pub fn add_wall(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        self.population.add_wall(Wall::new(x1, y1, x2, y2));
+    }
+    /// Places a closed polygon of walls, `points` holds the corners as `x0, y0, x1, y1, ...`
+    pub fn add_polygon(&mut self, points: Vec<f32>) {
+        for wall in Wall::polygon(&points) {
+            self.population.add_wall(wall);
+        }
+    }
     /// # Returns the width of the world
     ///
     /// ```
@@ -97,7 +403,7 @@ impl World {
     /// assert_eq!(world.get_width(), 128.0);
     /// ```
     pub fn get_width(&self) -> f32 {
-        self.width
+        self.dimensions.width
     }
     /// # Returns the height of the world
     ///
@@ -109,28 +415,172 @@ impl World {
     /// assert_eq!(world.get_height(), 256.0);
     /// ```
     pub fn get_height(&self) -> f32 {
-        self.height
+        self.dimensions.height
+    }
+    /// Adds an intervention policy, returns its index as used in the history
+    pub fn add_policy(&mut self, policy: Policy) -> usize {
+        self.policies.push(policy);
+        self.policies.len() - 1
+    }
+    pub fn add_campaign(&mut self, campaign: Campaign) {
+        self.campaigns.push(campaign);
+    }
+    pub fn set_testing(&mut self, testing: Testing) {
+        self.testing = Some(testing);
+    }
+    /// Traces the contacts of the cases that testing detects
+    pub fn set_tracing(&mut self, tracing: Tracing) {
+        self.tracing = Some(tracing);
+    }
+    /// Calls `callback` with the `Stats` every tick. It returns an object with the factors
+    /// `move_speed`, `distance` and `infection_rate` for that tick, missing factors stay 1.
+    /// A callback that throws is reported on the console and not called anymore.
+    pub fn set_controller_callback(&mut self, callback: js_sys::Function) {
+        let mut failed = false;
+        self.set_controller(move |stats| {
+            if failed {
+                return Modifiers::none();
+            }
+            let result = match callback.call1(&JsValue::NULL, &JsValue::from(*stats)) {
+                Ok(result) => result,
+                Err(error) => {
+                    failed = true;
+                    web_sys::console::error_2(&JsValue::from_str("The controller failed and is stopped:"), &error);
+                    return Modifiers::none();
+                }
+            };
+            let factor = |name: &str| {
+                js_sys::Reflect::get(&result, &JsValue::from_str(name))
+                    .ok()
+                    .and_then(|value| value.as_f64())
+                    .map_or(1.0, |value| value as f32)
+            };
+            Modifiers {
+                move_speed: factor("move_speed"),
+                distance: factor("distance"),
+                infection_rate: factor("infection_rate"),
+            }
+        });
+    }
+    pub fn get_tick(&self) -> usize {
+        self.tick
     }
     pub fn update(&mut self) {
-        self.population.update_positions(self.move_speed);
-        self.population.infect_closeby();
+        let stats = self.get_stats();
+        let mut active = Vec::new();
+        let mut active_policies = Vec::new();
+        for (index, policy) in self.policies.iter_mut().enumerate() {
+            if policy.update(self.tick, &stats) {
+                active.push(policy.clone());
+                active_policies.push(index);
+            }
+        }
+        if let Some(controller) = &mut self.controller {
+            active.extend(controller(&stats).to_policies());
+        }
+        if self.distance_changed {
+            self.population.fit_index(self.strains.get(self.strain).distance);
+            self.distance_changed = false;
+        }
+        self.vaccinate();
+        let detected = match &mut self.testing {
+            Some(testing) => testing.update(self.tick, &mut self.population),
+            None => Vec::new(),
+        };
+        if let Some(tracing) = &mut self.tracing {
+            tracing.record(self.tick, &self.population);
+            tracing.trace(self.tick, &detected, &mut self.population);
+        }
+        self.population.update_positions(self.move_speed, &active, &self.strains);
+        self.averted += self.population.infect_closeby(&active, &self.strains);
+        self.update_demographics();
+        self.history.push_back(Record {
+            tick: self.tick,
+            stats,
+            active_policies,
+        });
+        self.trim_history();
+        self.tick += 1;
     }
-    pub fn get_stats(&self) -> Stats {
-        let mut count: (usize, usize, usize) = (0, 0, 0);
-        for person in self.population.iter() {
-            match person.get_state() {
-                PersonState::Susceptible => count.0 += 1,
-                PersonState::Infectious(_virus) => count.1 += 1,
-                PersonState::Recovered(_dead) => count.2 += 1,
+    // Children are born at the home of a random living person
+    fn update_demographics(&mut self) {
+        let demographics = match &mut self.demographics {
+            Some(demographics) => demographics,
+            None => return,
+        };
+        let mut rng = rand::thread_rng();
+        let living: Vec<(usize, Location)> = self
+            .population
+            .iter()
+            .filter(|person| person.get_state() != PersonState::Recovered(true))
+            .map(|person| (person.get_id(), person.get_home().clone()))
+            .collect();
+        let dying: Vec<usize> = living
+            .iter()
+            .filter(|_| rng.gen_range(0.0, 1.0) < demographics.death_rate)
+            .map(|(id, _)| *id)
+            .collect();
+        let births = demographics.births(living.len());
+        for (_, home) in living.choose_multiple(&mut rng, births) {
+            self.population.add_new(home.x, home.y).years = 0;
+        }
+        for id in dying.iter() {
+            self.population.remove(*id);
+        }
+        self.births += births.min(living.len());
+        self.deaths += dying.len();
+    }
+    fn vaccinate(&mut self) {
+        for campaign in self.campaigns.iter() {
+            for id in campaign.select(self.tick, &self.population) {
+                if let Some(mut person) = self.population.get_mut(id) {
+                    person.vaccinate(campaign.protection());
+                }
             }
         }
-        let total = (count.0 + count.1 + count.2) as f32;
+    }
+    /// Keeps the records of the last `limit` ticks, 0 keeps no history at all
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        self.trim_history();
+    }
+    fn trim_history(&mut self) {
+        while self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+    }
+    /// The number of ticks in the history, at most the history limit
+    pub fn get_history_length(&self) -> usize {
+        self.history.len()
+    }
+    /// The stats at the start of tick `tick`, none for ticks that aren't in the history
+    pub fn get_history_stats(&self, tick: usize) -> Option<Stats> {
+        self.record(tick).map(|record| record.stats)
+    }
+    /// The indexes of the policies that were active during tick `tick`, none for ticks that aren't in the history
+    pub fn get_history_policies(&self, tick: usize) -> Option<Vec<u32>> {
+        self.record(tick).map(|record| record.active_policies.iter().map(|index| *index as u32).collect())
+    }
+    pub fn get_stats(&self) -> Stats {
+        let counts = self.population.counts();
+        let total = counts.total() as f32;
         Stats {
-            susceptable: count.0 as f32 / total,
-            infected: count.1 as f32 / total,
-            recovered: count.2 as f32 / total,
+            susceptable: counts.susceptible as f32 / total,
+            exposed: counts.exposed as f32 / total,
+            infected: counts.infectious as f32 / total,
+            recovered: counts.recovered as f32 / total,
+            vaccinated: counts.vaccinated as f32 / total,
+            cases: counts.cases as f32 / total,
+            detected: counts.detected as f32 / total,
+            isolated: counts.isolated as f32 / total,
+            averted: self.averted / total,
+            incidence: self.population.incidence() as f32 / total,
         }
     }
+    /// The number of people that got infected during the last tick
+    pub fn get_incidence(&self) -> usize {
+        self.population.incidence()
+    }
     pub fn render(&self, canvas_id: &str) {
         let document = web_sys::window().unwrap().document().unwrap();
         let canvas = document.get_element_by_id(canvas_id).unwrap();
@@ -146,15 +596,28 @@ impl World {
             .dyn_into::<web_sys::CanvasRenderingContext2d>()
             .unwrap();
         let red = "#ff0000";
+        let orange = "#ff8800";
+        let cyan = "#00ffff";
         let green = "#00ff00";
         let blue = "#0000ff";
         let white = "#ffffff";
         let black = "#000000";
+        let grey = "#888888";
         context.set_fill_style(&JsValue::from_str(black));
-        context.fill_rect(0.0, 0.0, self.width as f64, self.height as f64);
+        context.fill_rect(0.0, 0.0, self.dimensions.width as f64, self.dimensions.height as f64);
+        context.set_stroke_style(&JsValue::from_str(grey));
+        context.set_line_width(2.0);
+        context.begin_path();
+        for wall in self.population.walls() {
+            context.move_to(wall.start.x as f64, wall.start.y as f64);
+            context.line_to(wall.end.x as f64, wall.end.y as f64);
+        }
+        context.stroke();
         for person in self.population.iter() {
             match person.get_state() {
+                PersonState::Susceptible if person.is_vaccinated() => context.set_fill_style(&JsValue::from_str(cyan)),
                 PersonState::Susceptible => context.set_fill_style(&JsValue::from_str(green)),
+                PersonState::Exposed(_virus) => context.set_fill_style(&JsValue::from_str(orange)),
                 PersonState::Infectious(_virus) => context.set_fill_style(&JsValue::from_str(red)),
                 PersonState::Recovered(false) => context.set_fill_style(&JsValue::from_str(blue)),
                 PersonState::Recovered(true) => context.set_fill_style(&JsValue::from_str(white)),
@@ -170,15 +633,77 @@ impl World {
 }
 
 impl World {
-    pub fn people(&self) -> std::iter::Flatten<Flatten<Iter<'_, Vec<Vec<Person>>>>> {
+    /// Copies of everybody
+    pub fn people(&self) -> impl Iterator<Item = Person> + '_ {
         self.population.iter()
     }
+    // Applies `change` to the people within `radius` of (x, y), returns for how many it made a change
+    fn update_within<F>(&mut self, x: f32, y: f32, radius: f32, mut change: F) -> usize
+    where
+        F: FnMut(&mut Person) -> bool,
+    {
+        let ids: Vec<usize> = self.population.people_within(x, y, radius).iter().map(|person| person.get_id()).collect();
+        let mut changed = 0;
+        for id in ids {
+            if let Some(mut person) = self.population.get_mut(id) {
+                if change(&mut person) {
+                    changed += 1;
+                }
+            }
+        }
+        changed
+    }
+    /// Uses `threads` threads for large populations, see `World::set_threads`
+    pub fn use_threads(&mut self, threads: usize) -> Result<(), String> {
+        self.population.set_threads(threads)
+    }
+    /// Lets `controller` adjust the simulation every tick, on top of the policies
+    pub fn set_controller<F>(&mut self, controller: F)
+    where
+        F: FnMut(&Stats) -> Modifiers + 'static,
+    {
+        self.controller = Some(Box::new(controller));
+    }
+    pub fn history(&self) -> vec_deque::Iter<'_, Record> {
+        self.history.iter()
+    }
+    /// The record of tick `tick`, if it is still in the history
+    pub fn record(&self, tick: usize) -> Option<&Record> {
+        let first = self.history.front()?.tick;
+        self.history.get(tick.checked_sub(first)?)
+    }
+    pub fn population_size(&self) -> usize {
+        self.population.len()
+    }
+    /// Takes a random living person out of this world, e.g. to travel to another world
+    pub fn emigrate(&mut self) -> Option<Person> {
+        self.population.take_random()
+    }
+    /// Every virus in this world
+    pub fn strains(&self) -> &Strains {
+        &self.strains
+    }
+    /// Welcomes a person from another world at a random place in this world,
+    /// `strains` are the viruses of the other world
+    pub fn immigrate(&mut self, mut person: Person, strains: &Strains) {
+        if let PersonState::Exposed(strain) | PersonState::Infectious(strain) = person.get_state() {
+            person.set_strain(self.strains.find_or_add(strains.get(strain)));
+        }
+        let mut rng = rand::thread_rng();
+        person.relocate(rng.gen_range(0.0, self.dimensions.width), rng.gen_range(0.0, self.dimensions.height));
+        self.population.add_with_next_id(person);
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sir::counts::Counts;
+    use crate::sir::policy::Intervention;
+    use crate::sir::testing::TestSelection;
+    use crate::sir::tracing::Tracing;
+    use crate::sir::vaccination::VaccinationStrategy;
 
     #[test]
     fn update_move_speed() {
@@ -189,7 +714,7 @@ mod tests {
         let mut max_move = 0.0;
         for _ in 1..10000 {
             world.update();
-            let dist = person.sqr_distance(&world.population.iter().next().unwrap(), 100.0, 100.0);
+            let dist = person.sqr_distance(&world.population.iter().next().unwrap(), &world.dimensions);
             if dist > max_move {
                 max_move = dist;
             }
@@ -198,4 +723,325 @@ mod tests {
         // There is a random factor in here
         assert!(max_move < 450.0 && max_move > 400.0);
     }
+
+    #[test]
+    fn households_share_a_home() {
+        let mut settings = DistributionSettings::new();
+        settings.household_size = 3;
+        let world = World::new_with_settings(30, 100.0, 100.0, Virus::corona(), PopulationDistribution::Households, settings);
+        let mut homes: Vec<(f32, f32)> = world.people().map(|person| (person.position.x, person.position.y)).collect();
+        assert_eq!(homes.len(), 30);
+        homes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        homes.dedup();
+        assert_eq!(homes.len(), 10);
+    }
+
+    #[test]
+    fn clusters_stay_close_to_their_center() {
+        let mut settings = DistributionSettings::new();
+        settings.cluster_count = 1;
+        settings.cluster_spread = 0.0;
+        let world = World::new_with_settings(20, 100.0, 100.0, Virus::corona(), PopulationDistribution::Clustered, settings);
+        let first = world.people().next().unwrap().clone();
+        for person in world.people() {
+            assert!(first.sqr_distance(&person, &world.dimensions) < 0.0001);
+        }
+    }
+
+    #[test]
+    fn patient_zero_is_always_infected() {
+        for _ in 0..20 {
+            let world = World::new(10, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+            for person in world.people() {
+                let infected = person.get_state() == PersonState::Infectious(world.strain);
+                assert_eq!(infected, person.get_id() == 0);
+            }
+        }
+    }
+
+    #[test]
+    fn seed_random_people() {
+        let mut world = World::new(100, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.seed(&Seeding::fractions(100, 0.1, 0.05, 0.2));
+        let stats = world.get_stats();
+        assert_eq!(stats.infected, 0.1);
+        assert_eq!(stats.exposed, 0.05);
+        assert_eq!(stats.recovered, 0.2);
+        assert_eq!(stats.susceptable, 0.65);
+    }
+
+    #[test]
+    fn seed_cluster_around_location() {
+        let mut world = World::new(100, 100.0, 100.0, Virus::corona(), PopulationDistribution::Grid);
+        let mut seeding = Seeding::new();
+        seeding.infectious = 1;
+        seeding.immune = 4;
+        seeding.selection = SeedSelection::Cluster;
+        seeding.x = 51.0;
+        seeding.y = 49.0;
+        world.seed(&seeding);
+        for person in world.people() {
+            let (x, y) = (person.position.x, person.position.y);
+            match person.get_state() {
+                PersonState::Infectious(_) => assert!(x == 50.0 && y == 50.0),
+                PersonState::Recovered(false) => assert!((x - 50.0).abs() + (y - 50.0).abs() == 10.0),
+                state => assert_eq!(state, PersonState::Susceptible),
+            }
+        }
+    }
+
+    #[test]
+    fn history_records_active_policies() {
+        let mut world = World::new(10, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.add_policy(Policy::scheduled(Intervention::Lockdown, 0.0, 1, 3));
+        world.add_policy(Policy::scheduled(Intervention::MaskMandate, 0.5, 2, 0));
+        for _ in 0..4 {
+            world.update();
+        }
+        assert_eq!(world.get_history_length(), 4);
+        assert_eq!(world.get_history_policies(0), Some(Vec::<u32>::new()));
+        assert_eq!(world.get_history_policies(1), Some(vec![0]));
+        assert_eq!(world.get_history_policies(2), Some(vec![0, 1]));
+        assert_eq!(world.get_history_policies(3), Some(vec![1]));
+        assert_eq!(world.get_history_policies(4), None);
+        assert!(world.get_history_stats(4).is_none());
+    }
+
+    #[test]
+    fn history_keeps_the_last_ticks() {
+        let mut world = World::new(10, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.set_history_limit(3);
+        for _ in 0..5 {
+            world.update();
+        }
+        assert_eq!(world.get_history_length(), 3);
+        assert!(world.get_history_stats(1).is_none());
+        assert_eq!(world.record(2).unwrap().tick, 2);
+        assert_eq!(world.history().map(|record| record.tick).collect::<Vec<usize>>(), vec![2, 3, 4]);
+        world.set_history_limit(0);
+        assert!(world.history().next().is_none());
+    }
+
+    #[test]
+    fn lockdown_stops_movement() {
+        let mut world = World::new(10, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.add_policy(Policy::scheduled(Intervention::Lockdown, 0.0, 0, 0));
+        let before: Vec<(usize, f32, f32)> = world.people().map(|p| (p.get_id(), p.position.x, p.position.y)).collect();
+        world.update();
+        for (id, x, y) in before {
+            let person = world.people().find(|person| person.get_id() == id).unwrap();
+            assert_eq!((person.position.x, person.position.y), (x, y));
+        }
+    }
+
+    #[test]
+    fn campaign_vaccinates_people_every_tick() {
+        let mut world = World::new(100, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.add_campaign(Campaign::new(2, 10, VaccinationStrategy::Random));
+        for _ in 0..5 {
+            world.update();
+        }
+        assert_eq!(world.get_stats().vaccinated, 0.3);
+    }
+
+    #[test]
+    fn detected_cases_are_reported() {
+        let mut virus = Virus::corona();
+        virus.infection_rate = 0.0;
+        let mut world = World::new(100, 100.0, 100.0, virus, PopulationDistribution::Random);
+        world.seed(&Seeding::fractions(100, 0.2, 0.0, 0.0));
+        let mut testing = Testing::new(100, TestSelection::Symptomatic);
+        testing.sensitivity = 1.0;
+        testing.delay = 0;
+        world.set_testing(testing);
+        assert_eq!((world.get_stats().cases, world.get_stats().detected), (0.2, 0.0));
+        world.update();
+        let stats = world.get_stats();
+        assert_eq!((stats.cases, stats.detected), (0.2, 0.2));
+        assert_eq!(stats.isolated, 0.2);
+    }
+
+    #[test]
+    fn tracing_averts_infections() {
+        let mut virus = Virus::corona();
+        virus.distance = 20.0;
+        virus.infection_rate = 0.5;
+        virus.incubation_time = 3;
+        let mut world = World::new(200, 100.0, 100.0, virus, PopulationDistribution::Grid);
+        // the exposed contacts of the cases become infectious while they are in quarantine
+        world.seed(&Seeding::fractions(200, 0.1, 0.5, 0.0));
+        let mut testing = Testing::new(200, TestSelection::Symptomatic);
+        testing.sensitivity = 1.0;
+        testing.delay = 0;
+        world.set_testing(testing);
+        let mut tracing = Tracing::new(1.0);
+        tracing.distance = 20.0;
+        tracing.delay = 0;
+        world.set_tracing(tracing);
+        for _ in 0..6 {
+            world.update();
+        }
+        assert!(world.get_stats().averted > 0.0);
+    }
+
+    #[test]
+    fn controller_locks_down_while_people_are_infected() {
+        let mut world = World::new(50, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.set_controller(|stats| {
+            let mut modifiers = Modifiers::none();
+            if stats.infected > 0.0 {
+                modifiers.move_speed = 0.0;
+            }
+            modifiers
+        });
+        let before: Vec<(usize, f32, f32)> = world.people().map(|person| (person.get_id(), person.position.x, person.position.y)).collect();
+        world.update();
+        for person in world.people() {
+            assert!(before.contains(&(person.get_id(), person.position.x, person.position.y)));
+        }
+    }
+
+    #[test]
+    fn virus_changes_reach_infected_people() {
+        let mut world = World::new(10, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.set_recovery_time(0);
+        world.set_mortality_rate(1.0);
+        world.update();
+        assert_eq!(world.get_virus().recovery_time, 0);
+        let patient_zero = world.people().find(|person| person.get_id() == 0).unwrap();
+        assert_eq!(patient_zero.get_state(), PersonState::Recovered(true));
+    }
+
+    #[test]
+    fn virus_changes_leave_other_viruses_alone() {
+        let mut world = World::new(10, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        let mut other = Virus::corona();
+        other.distance = 20.0;
+        let mut strains = Strains::new();
+        let strain = strains.add(other);
+        let mut traveller = Person::new(50.0, 50.0, 0);
+        traveller.set_state(PersonState::Infectious(strain));
+        world.immigrate(traveller, &strains);
+        world.set_distance(5.0);
+        world.update();
+        let carried: Vec<f32> = world
+            .people()
+            .filter_map(|person| match person.get_state() {
+                PersonState::Exposed(strain) | PersonState::Infectious(strain) => Some(world.strains().get(strain).distance),
+                _ => None,
+            })
+            .collect();
+        assert!(carried.contains(&5.0) && carried.contains(&20.0));
+    }
+
+    #[test]
+    fn edit_people_around_a_location() {
+        let mut world = World::new(100, 100.0, 100.0, Virus::corona(), PopulationDistribution::Grid);
+        world.seed(&Seeding::new());
+        let infected = world.infect_within(50.0, 50.0, 15.0);
+        assert!(infected > 0);
+        assert_eq!(world.infect_within(50.0, 50.0, 15.0), 0);
+        assert_eq!(world.get_stats().infected, infected as f32 / 100.0);
+        assert_eq!(world.immunise_within(50.0, 50.0, 15.0), 0);
+        assert!(world.vaccinate_within(10.0, 10.0, 15.0, 0.9) > 0);
+        let removed = world.remove_within(50.0, 50.0, 15.0);
+        assert_eq!(removed, infected);
+        assert_eq!(world.get_stats().infected, 0.0);
+        let id = world.add_person(50.0, 50.0);
+        assert_eq!(id, 100);
+        assert_eq!(world.nearest_person(50.0, 50.0), Some(id));
+    }
+
+    #[test]
+    fn infections_reach_further_after_the_distance_grows() {
+        let mut virus = Virus::corona();
+        virus.infection_rate = 1.0;
+        let mut world = World::new(0, 100.0, 100.0, virus, PopulationDistribution::Random);
+        world.config(0.0);
+        let patient = world.add_person(50.0, 50.0);
+        let contact = world.add_person(80.0, 50.0);
+        let mut seeding = Seeding::new();
+        seeding.infectious = 1;
+        seeding.selection = SeedSelection::Ids;
+        seeding.set_ids(vec![patient]);
+        world.seed(&seeding);
+        world.update();
+        assert_eq!(world.population.get(contact).unwrap().get_state(), PersonState::Susceptible);
+        world.set_distance(40.0);
+        world.update();
+        assert_ne!(world.population.get(contact).unwrap().get_state(), PersonState::Susceptible);
+    }
+
+    #[test]
+    fn counts_stay_the_same_as_counting_everybody() {
+        let mut virus = Virus::corona();
+        virus.distance = 15.0;
+        virus.incubation_time = 2;
+        virus.recovery_time = 5;
+        virus.mortality_rate = 0.3;
+        let mut world = World::new(300, 100.0, 100.0, virus, PopulationDistribution::Clustered);
+        world.use_threads(2).unwrap();
+        world.seed(&Seeding::fractions(300, 0.1, 0.1, 0.0));
+        let mut testing = Testing::new(20, TestSelection::Symptomatic);
+        testing.delay = 1;
+        world.set_testing(testing);
+        world.set_tracing(Tracing::new(0.5));
+        world.add_campaign(Campaign::new(2, 5, VaccinationStrategy::Random));
+        world.set_demographics(Demographics::new(0.02, 0.02));
+        for tick in 0..30 {
+            if tick == 10 {
+                world.set_recovery_time(2);
+                world.infect_within(50.0, 50.0, 20.0);
+                world.remove_within(20.0, 20.0, 10.0);
+            }
+            world.update();
+            let mut expected = Counts::default();
+            for person in world.people() {
+                expected += Counts::of(&person);
+            }
+            assert_eq!(world.population.counts(), expected, "tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn incidence_counts_the_new_infections_of_the_last_tick() {
+        let mut virus = Virus::corona();
+        virus.infection_rate = 1.0;
+        let mut world = World::new(0, 100.0, 100.0, virus, PopulationDistribution::Random);
+        world.config(0.0);
+        let patient = world.add_person(50.0, 50.0);
+        for _ in 0..3 {
+            world.add_person(52.0, 50.0);
+        }
+        world.add_person(10.0, 10.0);
+        let mut seeding = Seeding::new();
+        seeding.infectious = 1;
+        seeding.selection = SeedSelection::Ids;
+        seeding.set_ids(vec![patient]);
+        world.seed(&seeding);
+        assert_eq!(world.get_incidence(), 0);
+        world.update();
+        assert_eq!(world.get_incidence(), 3);
+        assert_eq!(world.get_stats().incidence, 0.6);
+        world.update();
+        assert_eq!(world.get_incidence(), 0);
+    }
+
+    #[test]
+    fn people_are_born_and_die() {
+        let mut world = World::new(100, 100.0, 100.0, Virus::corona(), PopulationDistribution::Random);
+        world.seed(&Seeding::new());
+        world.set_demographics(Demographics::new(0.05, 0.05));
+        for _ in 0..10 {
+            world.update();
+        }
+        assert!(world.get_births() > 0);
+        assert!(world.get_deaths() > 0);
+        assert_eq!(world.population_size(), 100 + world.get_births() - world.get_deaths());
+        let mut ids: Vec<usize> = world.people().map(|person| person.get_id()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), world.population_size());
+        assert!(world.people().any(|person| person.get_id() >= 100 && person.years == 0));
+    }
 }
@@ -0,0 +1,119 @@
+//! Properties of the wrap-around geometry that hold in any rectangular world
+#![cfg(not(target_arch = "wasm32"))]
+use proptest::prelude::*;
+use sir::sir::geometry::{Dimensions, Grid};
+use sir::sir::person::{Location, Person, PersonState};
+use sir::sir::population::Population;
+use sir::sir::virus::{Strains, Virus};
+
+fn world() -> impl Strategy<Value = Dimensions> {
+    (1.0f32..2000.0, 1.0f32..2000.0).prop_map(|(width, height)| Dimensions::new(width, height))
+}
+
+// Locations as a fraction of the size of the world, also outside of it
+fn fraction() -> impl Strategy<Value = (f32, f32)> {
+    (-3.0f32..3.0, -3.0f32..3.0)
+}
+
+fn at(world: &Dimensions, (x, y): (f32, f32)) -> Location {
+    Location { x: x * world.width, y: y * world.height }
+}
+
+proptest! {
+    #[test]
+    fn wrapped_locations_lie_in_the_world(world in world(), location in fraction()) {
+        let location = at(&world, location);
+        let wrapped = world.wrap(&location);
+        prop_assert!(wrapped.x >= 0.0 && wrapped.x < world.width);
+        prop_assert!(wrapped.y >= 0.0 && wrapped.y < world.height);
+        // it is the same place
+        let size = world.width.max(world.height);
+        prop_assert!(world.sqr_distance(&location, &wrapped).sqrt() < size * 1e-5);
+    }
+
+    #[test]
+    fn delta_is_the_shortest_way(world in world(), from in fraction(), to in fraction()) {
+        let from = at(&world, from);
+        let to = at(&world, to);
+        let (diff_x, diff_y) = world.delta(&from, &to);
+        let size = world.width.max(world.height);
+        prop_assert!(diff_x.abs() <= world.width / 2.0 + size * 1e-5);
+        prop_assert!(diff_y.abs() <= world.height / 2.0 + size * 1e-5);
+        let arrived = Location { x: from.x + diff_x, y: from.y + diff_y };
+        prop_assert!(world.sqr_distance(&arrived, &to).sqrt() < size * 1e-5);
+        let back = world.sqr_distance(&to, &from).sqrt();
+        prop_assert!((world.sqr_distance(&from, &to).sqrt() - back).abs() < size * 1e-5);
+    }
+
+    #[test]
+    fn locations_fall_in_their_cell(world in world(), columns in 1usize..200, rows in 1usize..200, location in fraction()) {
+        let grid = Grid::new(world, columns, rows);
+        let location = at(&world, location);
+        let (column, row) = grid.cell(location.x, location.y);
+        prop_assert!(column < columns && row < rows);
+        let wrapped = world.wrap(&location);
+        let (width, height) = (grid.cell_width(), grid.cell_height());
+        prop_assert!(column as f32 * width <= wrapped.x + width * 1e-3);
+        prop_assert!(wrapped.x < (column + 1) as f32 * width + width * 1e-3);
+        prop_assert!(row as f32 * height <= wrapped.y + height * 1e-3);
+        prop_assert!(wrapped.y < (row + 1) as f32 * height + height * 1e-3);
+    }
+
+    #[test]
+    fn nearby_cells_cover_the_distance(
+        world in world(),
+        columns in 1usize..200,
+        rows in 1usize..200,
+        distance in 0.1f32..500.0,
+        location in fraction(),
+        direction in 0.0f32..std::f32::consts::TAU,
+        part in 0.0f32..0.99,
+    ) {
+        let grid = Grid::new(world, columns, rows);
+        let from = at(&world, location);
+        let to = Location {
+            x: from.x + direction.cos() * distance * part,
+            y: from.y + direction.sin() * distance * part,
+        };
+        let (from_column, from_row) = grid.cell(from.x, from.y);
+        let (to_column, to_row) = grid.cell(to.x, to.y);
+        let columns_within = grid.columns_within(from_column, distance);
+        let rows_within = grid.rows_within(from_row, distance);
+        prop_assert!(columns_within.iter().any(|column| column.rem_euclid(columns as isize) as usize == to_column));
+        prop_assert!(rows_within.iter().any(|row| row.rem_euclid(rows as isize) as usize == to_row));
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn infections_reach_everybody_within_the_distance(
+        world in world(),
+        columns in 1usize..60,
+        rows in 1usize..60,
+        distance in 1.0f32..300.0,
+        locations in prop::collection::vec((0.0f32..1.0, 0.0f32..1.0), 1..150),
+    ) {
+        let mut virus = Virus::corona();
+        virus.distance = distance;
+        virus.infection_rate = 1.0;
+        let mut strains = Strains::new();
+        let strain = strains.add(virus);
+        let mut population = Population::new(world.width, world.height, columns, rows);
+        for (index, location) in locations.iter().enumerate() {
+            let location = world.wrap(&at(&world, *location));
+            let mut person = Person::new(location.x, location.y, index);
+            if index % 10 == 0 {
+                person.set_state(PersonState::Infectious(strain));
+            }
+            population.add(person).unwrap();
+        }
+        let infectious: Vec<Person> = population.iter().filter(|person| person.get_id() % 10 == 0).collect();
+        population.infect_closeby(&[], &strains);
+        for person in population.iter() {
+            let reached = infectious.iter().any(|source| source.sqr_distance(&person, &world) < distance * distance);
+            prop_assert_eq!(person.get_state() != PersonState::Susceptible, reached, "person {}", person.get_id());
+        }
+    }
+}